@@ -1,36 +1,54 @@
-use std::fs;
+use std::{cmp::Reverse, env, fs};
+
+const DIAL_SIZE: i32 = 100;
+const START_POSITION: i32 = 50;
 
 fn main() {
     let rotations: Vec<Rotation> = parse_input("./src/input");
-    let mut position_ends_on_zero = 0;
-    let mut clicks_on_zero = 0;
 
-    rotations.iter().fold(50, |position: i32, rotation| {
-        let mut next_position = match rotation {
-            Rotation::Left(distance) => position - distance,
-            Rotation::Right(distance) => position + distance,
-        };
-
-        clicks_on_zero += count_click_on_zero(position, next_position);
-        next_position = next_position.rem_euclid(100);
-
-        if next_position == 0 {
-            position_ends_on_zero += 1;
+    match env::args().nth(1).as_deref() {
+        Some("histogram") => {
+            let histogram = dwell_histogram(&rotations, START_POSITION, DIAL_SIZE);
+            print!("{}", render_histogram(&histogram, 60));
         }
+        Some("what-if") => {
+            let top = env::args()
+                .nth(2)
+                .map(|value| value.parse().expect("Invalid number of changes"))
+                .unwrap_or(10);
+            print!("{}", render_what_if(&rotations, &what_if(&rotations), top));
+        }
+        _ => {
+            let (position_ends_on_zero, clicks_on_zero) = count_zeros(&rotations);
 
-        next_position
-    });
-
-    dbg!(position_ends_on_zero); // 1007
-    dbg!(clicks_on_zero); // 5820
+            dbg!(position_ends_on_zero); // 1007
+            dbg!(clicks_on_zero); // 5820
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Rotation {
     Left(i32),
     Right(i32),
 }
 
+impl Rotation {
+    fn delta(&self) -> i32 {
+        match self {
+            Rotation::Left(distance) => -distance,
+            Rotation::Right(distance) => *distance,
+        }
+    }
+
+    fn flipped(&self) -> Rotation {
+        match self {
+            Rotation::Left(distance) => Rotation::Right(*distance),
+            Rotation::Right(distance) => Rotation::Left(*distance),
+        }
+    }
+}
+
 fn parse_line(line: &str) -> Rotation {
     if let Some(distance) = line.strip_prefix("L") {
         return Rotation::Left(distance.parse().unwrap());
@@ -42,12 +60,37 @@ fn parse_line(line: &str) -> Rotation {
     panic!("Unexpected line format")
 }
 
+fn parse(input: &str) -> Vec<Rotation> {
+    input.lines().map(parse_line).collect()
+}
+
 fn parse_input(path: &str) -> Vec<Rotation> {
-    fs::read_to_string(path)
-        .unwrap()
-        .lines()
-        .map(parse_line)
-        .collect()
+    parse(&fs::read_to_string(path).unwrap())
+}
+
+fn count_zeros(rotations: &[Rotation]) -> (i32, i32) {
+    let mut position_ends_on_zero = 0;
+    let mut clicks_on_zero = 0;
+
+    rotations
+        .iter()
+        .fold(START_POSITION, |position: i32, rotation| {
+            let mut next_position = match rotation {
+                Rotation::Left(distance) => position - distance,
+                Rotation::Right(distance) => position + distance,
+            };
+
+            clicks_on_zero += count_click_on_zero(position, next_position);
+            next_position = next_position.rem_euclid(DIAL_SIZE);
+
+            if next_position == 0 {
+                position_ends_on_zero += 1;
+            }
+
+            next_position
+        });
+
+    (position_ends_on_zero, clicks_on_zero)
 }
 
 fn count_click_on_zero(position: i32, next_position: i32) -> i32 {
@@ -58,10 +101,278 @@ fn count_click_on_zero(position: i32, next_position: i32) -> i32 {
 
     while i != next_position {
         i += step;
-        if i.rem_euclid(100) == 0 {
+        if i.rem_euclid(DIAL_SIZE) == 0 {
             result += 1;
         }
     }
 
     result
 }
+
+// Same as `count_click_on_zero`, but for a position already on the dial and
+// without walking click by click.
+fn zero_clicks(position: i32, rotation: &Rotation) -> i32 {
+    match rotation {
+        Rotation::Right(distance) => (position + distance).div_euclid(DIAL_SIZE),
+        Rotation::Left(distance) => {
+            (position - 1).div_euclid(DIAL_SIZE) - (position - distance - 1).div_euclid(DIAL_SIZE)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Remove,
+    Flip,
+}
+
+#[derive(Debug, PartialEq)]
+struct WhatIf {
+    index: usize,
+    change: Change,
+    position_ends_on_zero: i32,
+    clicks_on_zero: i32,
+}
+
+// Evaluates removing or flipping every rotation. A change at index `i` leaves
+// the prefix untouched and shifts every later position by a constant, so the
+// totals are the prefix totals, the changed rotation itself, and a suffix table
+// indexed by that shift.
+fn what_if(rotations: &[Rotation]) -> Vec<WhatIf> {
+    let size = DIAL_SIZE as usize;
+
+    let mut positions = vec![START_POSITION.rem_euclid(DIAL_SIZE)];
+    for rotation in rotations {
+        let position = positions[positions.len() - 1];
+        positions.push((position + rotation.delta()).rem_euclid(DIAL_SIZE));
+    }
+
+    let mut prefix = vec![(0, 0)];
+    for (index, rotation) in rotations.iter().enumerate() {
+        let (ends_on_zero, clicks_on_zero) = prefix[index];
+        prefix.push((
+            ends_on_zero + (positions[index + 1] == 0) as i32,
+            clicks_on_zero + zero_clicks(positions[index], rotation),
+        ));
+    }
+
+    let mut suffix = vec![(0, 0); (rotations.len() + 1) * size];
+    for (index, rotation) in rotations.iter().enumerate().rev() {
+        for shift in 0..size {
+            let position = (positions[index] + shift as i32) % DIAL_SIZE;
+            let next_position = (positions[index + 1] + shift as i32) % DIAL_SIZE;
+            let (ends_on_zero, clicks_on_zero) = suffix[(index + 1) * size + shift];
+            suffix[index * size + shift] = (
+                ends_on_zero + (next_position == 0) as i32,
+                clicks_on_zero + zero_clicks(position, rotation),
+            );
+        }
+    }
+
+    let mut result = vec![];
+    for (index, rotation) in rotations.iter().enumerate() {
+        let (prefix_ends_on_zero, prefix_clicks_on_zero) = prefix[index];
+        let suffix_at =
+            |shift: i32| suffix[(index + 1) * size + shift.rem_euclid(DIAL_SIZE) as usize];
+
+        let (ends_on_zero, clicks_on_zero) = suffix_at(-rotation.delta());
+        result.push(WhatIf {
+            index,
+            change: Change::Remove,
+            position_ends_on_zero: prefix_ends_on_zero + ends_on_zero,
+            clicks_on_zero: prefix_clicks_on_zero + clicks_on_zero,
+        });
+
+        let flipped = rotation.flipped();
+        let flipped_ends_on_zero =
+            ((positions[index] + flipped.delta()).rem_euclid(DIAL_SIZE) == 0) as i32;
+        let (ends_on_zero, clicks_on_zero) = suffix_at(-2 * rotation.delta());
+        result.push(WhatIf {
+            index,
+            change: Change::Flip,
+            position_ends_on_zero: prefix_ends_on_zero + flipped_ends_on_zero + ends_on_zero,
+            clicks_on_zero: prefix_clicks_on_zero
+                + zero_clicks(positions[index], &flipped)
+                + clicks_on_zero,
+        });
+    }
+
+    result.sort_by_key(|what_if| {
+        (
+            Reverse(what_if.clicks_on_zero),
+            Reverse(what_if.position_ends_on_zero),
+            what_if.index,
+        )
+    });
+    result
+}
+
+fn render_what_if(rotations: &[Rotation], what_ifs: &[WhatIf], top: usize) -> String {
+    let (position_ends_on_zero, clicks_on_zero) = count_zeros(rotations);
+    let mut result = format!(
+        "baseline: {position_ends_on_zero} ends on zero, {clicks_on_zero} clicks on zero\n"
+    );
+
+    for what_if in what_ifs.iter().take(top) {
+        result += &format!(
+            "{:?} #{} ({:?}): {} ends on zero ({:+}), {} clicks on zero ({:+})\n",
+            what_if.change,
+            what_if.index,
+            rotations[what_if.index],
+            what_if.position_ends_on_zero,
+            what_if.position_ends_on_zero - position_ends_on_zero,
+            what_if.clicks_on_zero,
+            what_if.clicks_on_zero - clicks_on_zero,
+        );
+    }
+
+    result
+}
+
+// Counts how many clicks land on each dial position. Every full turn of a
+// rotation hits all positions once, and the remaining clicks cover a single
+// contiguous (possibly wrapping) arc, which is recorded in a difference array.
+fn dwell_histogram(rotations: &[Rotation], start: i32, dial_size: i32) -> Vec<u64> {
+    let size = dial_size as usize;
+    let mut full_turns: u64 = 0;
+    let mut differences = vec![0_i64; size + 1];
+    let mut position = start.rem_euclid(dial_size) as usize;
+
+    let mut add_arc = |from: usize, length: usize| {
+        if from + length <= size {
+            differences[from] += 1;
+            differences[from + length] -= 1;
+        } else {
+            differences[from] += 1;
+            differences[size] -= 1;
+            differences[0] += 1;
+            differences[from + length - size] -= 1;
+        }
+    };
+
+    for rotation in rotations {
+        let distance = match rotation {
+            Rotation::Left(distance) | Rotation::Right(distance) => *distance as usize,
+        };
+        full_turns += (distance / size) as u64;
+        let remaining_clicks = distance % size;
+
+        position = match rotation {
+            Rotation::Right(_) => {
+                add_arc((position + 1) % size, remaining_clicks);
+                (position + remaining_clicks) % size
+            }
+            Rotation::Left(_) => {
+                let next_position = (position + size - remaining_clicks) % size;
+                add_arc(next_position, remaining_clicks);
+                next_position
+            }
+        };
+    }
+
+    let mut running = 0_i64;
+    differences[..size]
+        .iter()
+        .map(|difference| {
+            running += difference;
+            full_turns + running as u64
+        })
+        .collect()
+}
+
+fn render_histogram(histogram: &[u64], width: usize) -> String {
+    let max = histogram.iter().copied().max().unwrap_or(0).max(1);
+    let label_width = (histogram.len().max(1) - 1).to_string().len();
+    let count_width = max.to_string().len();
+
+    histogram
+        .iter()
+        .enumerate()
+        .map(|(position, count)| {
+            let bar = "#".repeat((count * width as u64).div_ceil(max) as usize);
+            format!("{position:>label_width$} {count:>count_width$} {bar}\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82";
+
+#[test]
+fn test_count_zeros_example() {
+    assert_eq!(count_zeros(&parse(EXAMPLE)), (3, 6));
+}
+
+#[test]
+fn test_dwell_histogram_matches_click_walk() {
+    let rotations = parse("L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82\nR250\nL301\nR0");
+    let histogram = dwell_histogram(&rotations, START_POSITION, DIAL_SIZE);
+
+    let mut expected = vec![0_u64; DIAL_SIZE as usize];
+    let mut position = START_POSITION;
+    for rotation in &rotations {
+        let (step, distance) = match rotation {
+            Rotation::Left(distance) => (-1, *distance),
+            Rotation::Right(distance) => (1, *distance),
+        };
+        for _ in 0..distance {
+            position = (position + step).rem_euclid(DIAL_SIZE);
+            expected[position as usize] += 1;
+        }
+    }
+
+    assert_eq!(histogram, expected);
+}
+
+#[test]
+fn test_dwell_histogram_zero_matches_clicks_on_zero() {
+    let histogram = dwell_histogram(&parse(EXAMPLE), START_POSITION, DIAL_SIZE);
+    assert_eq!(histogram[0], 6);
+}
+
+#[test]
+fn test_render_histogram() {
+    assert_eq!(render_histogram(&[0, 2, 4], 4), "0 0 \n1 2 ##\n2 4 ####\n");
+}
+
+#[test]
+fn test_zero_clicks_matches_click_walk() {
+    for position in 0..DIAL_SIZE {
+        for distance in [0, 1, 49, 50, 99, 100, 101, 250] {
+            for rotation in [Rotation::Left(distance), Rotation::Right(distance)] {
+                assert_eq!(
+                    zero_clicks(position, &rotation),
+                    count_click_on_zero(position, position + rotation.delta())
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_what_if_matches_refold() {
+    let rotations = parse(EXAMPLE);
+    let what_ifs = what_if(&rotations);
+    assert_eq!(what_ifs.len(), rotations.len() * 2);
+
+    for what_if in &what_ifs {
+        let mut changed = rotations.clone();
+        match what_if.change {
+            Change::Remove => {
+                changed.remove(what_if.index);
+            }
+            Change::Flip => changed[what_if.index] = changed[what_if.index].flipped(),
+        }
+
+        assert_eq!(
+            (what_if.position_ends_on_zero, what_if.clicks_on_zero),
+            count_zeros(&changed)
+        );
+    }
+
+    assert!(
+        what_ifs
+            .windows(2)
+            .all(|pair| pair[0].clicks_on_zero >= pair[1].clicks_on_zero)
+    );
+}
@@ -1,38 +1,55 @@
-use std::fs;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    ops::RangeInclusive,
+};
 
 fn main() {
-    let ids: Vec<u64> = parse_input("./src/input")
-        .iter()
-        .flat_map(|range| range.start..=range.end)
-        .collect();
+    let ranges = parse_input("./src/input");
 
-    let step1_invalid_ids: u64 = ids
-        .iter()
-        .filter(|value| {
-            let string_value = value.to_string();
-            let pattern_size = string_value.len().div_euclid(2);
-            if string_value.len().rem_euclid(2) != 0 {
-                return false;
-            }
-            has_repeated_pattern(&string_value, pattern_size)
-        })
-        .sum();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|mode| mode.as_str()) {
+        Some("exactly") | Some("at-least") => {
+            let repeats: u32 = args
+                .get(1)
+                .expect("Missing number of repeats")
+                .parse()
+                .expect("Invalid number of repeats");
+            let tally = ranges
+                .iter()
+                .try_fold(Tally::default(), |tally, range| {
+                    tally.add(match args[0].as_str() {
+                        "exactly" => exact_repeats_tally(range, repeats)?,
+                        _ => at_least_repeats_tally(range, repeats)?,
+                    })
+                })
+                .expect(SUM_OVERFLOW);
+            dbg!(tally);
+            return;
+        }
+        Some("rule") => {
+            let rule = parse_rule(&args[1..].join(" "));
+            let invalid_ids = invalid_ids_sum(&ranges, rule.as_ref()).expect(SUM_OVERFLOW);
+            dbg!(invalid_ids);
+            return;
+        }
+        Some("report") => {
+            let report = range_report(&ranges, |pattern_count| pattern_count >= 2);
+            print!("{}", render_range_report(&ranges, &report));
+            return;
+        }
+        _ => {}
+    }
 
-    dbg!(step1_invalid_ids); // 40055209690
+    let step1_invalid_ids =
+        repeated_pattern_ids_sum(&ranges, |pattern_count| pattern_count == 2).expect(SUM_OVERFLOW);
 
-    let step2_invalid_ids: u64 = ids
-        .iter()
-        .filter(|value| {
-            let string_value = value.to_string();
-            if string_value.len() <= 1 {
-                return false;
-            }
-            let max_pattern_size = string_value.len().div_euclid(2);
+    dbg!(step1_invalid_ids); // 40055209690
 
-            (1..=max_pattern_size)
-                .any(|pattern_size| has_repeated_pattern(&string_value, pattern_size))
-        })
-        .sum();
+    let step2_invalid_ids =
+        repeated_pattern_ids_sum(&ranges, |pattern_count| pattern_count >= 2).expect(SUM_OVERFLOW);
 
     dbg!(step2_invalid_ids); // 50857215650
 }
@@ -50,21 +67,1037 @@ fn has_repeated_pattern(string: &str, pattern_size: usize) -> bool {
     string[0..pattern_size].repeat(pattern_count) == string
 }
 
+fn to_digits(value: Id, base: u32) -> String {
+    if value == 0 {
+        return "0".to_owned();
+    }
+
+    let mut digits = vec![];
+    let mut value = value;
+    while value > 0 {
+        digits.push(char::from_digit((value % base as Id) as u32, base).unwrap());
+        value /= base as Id;
+    }
+    digits.iter().rev().collect()
+}
+
+fn digit_count(value: Id, base: u32) -> u32 {
+    value.checked_ilog(base as Id).unwrap_or(0) + 1
+}
+
+// The number `1 0..0 1 0..0 1` (in `base`) that repeats a `pattern_size`
+// digits seed `pattern_count` times when multiplied with it.
+fn repetition_multiplier(pattern_size: u32, pattern_count: u32, base: u32) -> Id {
+    (0..pattern_count)
+        .map(|index| (base as Id).pow(pattern_size * index))
+        .sum()
+}
+
+fn smallest_with_digits(digits: u32, base: u32) -> Id {
+    if digits == 1 {
+        0
+    } else {
+        (base as Id).pow(digits - 1)
+    }
+}
+
+fn largest_with_digits(digits: u32, base: u32) -> Id {
+    (base as Id)
+        .checked_pow(digits)
+        .map_or(Id::MAX, |value| value - 1)
+}
+
+// The ids of the range with `digits` digits that repeat a `pattern_size`
+// digits seed are `seed * multiplier` for every seed in `first_seed..=last_seed`.
+fn seed_bounds(range: &Range, digits: u32, pattern_size: u32) -> Option<(Id, Id, Id)> {
+    let low = range.start.max(smallest_with_digits(digits, range.base));
+    let high = range.end.min(largest_with_digits(digits, range.base));
+    if low > high {
+        return None;
+    }
+
+    let multiplier = repetition_multiplier(pattern_size, digits / pattern_size, range.base);
+    let first_seed = low
+        .div_ceil(multiplier)
+        .max(smallest_with_digits(pattern_size, range.base));
+    let last_seed = (high / multiplier).min(largest_with_digits(pattern_size, range.base));
+    if first_seed > last_seed {
+        return None;
+    }
+
+    Some((first_seed, last_seed, multiplier))
+}
+
+// Builds every id of the range made of a pattern repeated a number of times
+// accepted by `accepts_pattern_count`, without walking the whole range.
+fn repeated_pattern_ids(range: &Range, accepts_pattern_count: impl Fn(u32) -> bool) -> Vec<Id> {
+    let mut ids = vec![];
+    if range.start > range.end {
+        return ids;
+    }
+
+    for digits in digit_count(range.start, range.base)..=digit_count(range.end, range.base) {
+        let pattern_sizes: Vec<u32> = (1..digits)
+            .filter(|pattern_size| {
+                digits.is_multiple_of(*pattern_size) && accepts_pattern_count(digits / pattern_size)
+            })
+            .collect();
+
+        for (index, pattern_size) in pattern_sizes.iter().enumerate() {
+            let Some((first_seed, last_seed, multiplier)) =
+                seed_bounds(range, digits, *pattern_size)
+            else {
+                continue;
+            };
+
+            for seed in first_seed..=last_seed {
+                let id = seed * multiplier;
+                // Ids that also repeat a smaller accepted pattern were already built.
+                let already_built = pattern_sizes[..index].iter().any(|smaller_pattern_size| {
+                    id.is_multiple_of(repetition_multiplier(
+                        *smaller_pattern_size,
+                        digits / smaller_pattern_size,
+                        range.base,
+                    ))
+                });
+                if !already_built {
+                    ids.push(id);
+                }
+            }
+        }
+    }
+
+    ids
+}
+
+const SUM_OVERFLOW: &str = "The sum of invalid ids doesn't fit in 128 bits";
+
+// Sums of ids close to `Id::MAX` don't fit in an `Id`, so sums are checked and
+// `None` when they overflow.
+fn checked_sum(values: impl IntoIterator<Item = Option<Id>>) -> Option<Id> {
+    values
+        .into_iter()
+        .try_fold(0 as Id, |sum, value| sum.checked_add(value?))
+}
+
+fn repeated_pattern_ids_sum(
+    ranges: &[Range],
+    accepts_pattern_count: impl Fn(u32) -> bool + Sync,
+) -> Option<Id> {
+    #[cfg(feature = "parallel")]
+    let ranges = ranges.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let ranges = ranges.iter();
+
+    let sums: Vec<Option<Id>> = ranges
+        .map(|range| {
+            checked_sum(
+                repeated_pattern_ids(range, &accepts_pattern_count)
+                    .into_iter()
+                    .map(Some),
+            )
+        })
+        .collect();
+    checked_sum(sums)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Tally {
+    count: Id,
+    sum: Id,
+}
+
+impl Tally {
+    fn add(self, other: Tally) -> Option<Tally> {
+        Some(Tally {
+            count: self.count.checked_add(other.count)?,
+            sum: self.sum.checked_add(other.sum)?,
+        })
+    }
+}
+
+fn mobius(value: u32) -> i32 {
+    let mut value = value;
+    let mut result = 1;
+    let mut factor = 2;
+    while factor * factor <= value {
+        if value.is_multiple_of(factor) {
+            value /= factor;
+            if value.is_multiple_of(factor) {
+                return 0;
+            }
+            result = -result;
+        }
+        factor += 1;
+    }
+    if value > 1 {
+        result = -result;
+    }
+    result
+}
+
+// Ids of the range with `digits` digits that have `pattern_size` as a period,
+// whether or not it is the smallest one.
+fn periodic_tally(range: &Range, digits: u32, pattern_size: u32) -> Option<Tally> {
+    let Some((first_seed, last_seed, multiplier)) = seed_bounds(range, digits, pattern_size) else {
+        return Some(Tally::default());
+    };
+
+    let count = (last_seed - first_seed).checked_add(1)?;
+    let seeds_sum = if count.is_multiple_of(2) {
+        (count / 2).checked_mul(first_seed.checked_add(last_seed)?)?
+    } else {
+        count.checked_mul(first_seed + (last_seed - first_seed) / 2)?
+    };
+    Some(Tally {
+        count,
+        sum: seeds_sum.checked_mul(multiplier)?,
+    })
+}
+
+// Ids of the range with `digits` digits whose smallest period is exactly
+// `pattern_size`. Every id with period `pattern_size` has a smallest period
+// dividing it, so Möbius inversion over the divisors removes the ids that are
+// also built from a smaller pattern (e.g. `111111` from `111`, `11` and `1`).
+fn smallest_period_tally(range: &Range, digits: u32, pattern_size: u32) -> Option<Tally> {
+    let mut added = Tally::default();
+    let mut removed = Tally::default();
+
+    for divisor in (1..=pattern_size).filter(|divisor| pattern_size.is_multiple_of(*divisor)) {
+        match mobius(pattern_size / divisor) {
+            1 => added = added.add(periodic_tally(range, digits, divisor)?)?,
+            -1 => removed = removed.add(periodic_tally(range, digits, divisor)?)?,
+            _ => {}
+        }
+    }
+
+    Some(Tally {
+        count: added.count - removed.count,
+        sum: added.sum - removed.sum,
+    })
+}
+
+fn repeats_tally(range: &Range, accepts_repeats: impl Fn(u32) -> bool) -> Option<Tally> {
+    let mut tally = Tally::default();
+    if range.start > range.end {
+        return Some(tally);
+    }
+
+    for digits in digit_count(range.start, range.base)..=digit_count(range.end, range.base) {
+        for pattern_size in (1..=digits).filter(|pattern_size| digits.is_multiple_of(*pattern_size))
+        {
+            if accepts_repeats(digits / pattern_size) {
+                tally = tally.add(smallest_period_tally(range, digits, pattern_size)?)?;
+            }
+        }
+    }
+
+    Some(tally)
+}
+
+// Ids of the range whose smallest repeating pattern appears exactly `repeats` times.
+fn exact_repeats_tally(range: &Range, repeats: u32) -> Option<Tally> {
+    repeats_tally(range, |pattern_count| pattern_count == repeats)
+}
+
+// Ids of the range whose smallest repeating pattern appears at least `repeats` times.
+fn at_least_repeats_tally(range: &Range, repeats: u32) -> Option<Tally> {
+    repeats_tally(range, |pattern_count| pattern_count >= repeats)
+}
+
+trait IdRule: Sync {
+    fn is_invalid(&self, id: Id, base: u32) -> bool;
+}
+
+struct RepeatedTwice;
+
+impl IdRule for RepeatedTwice {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        let string_value = to_digits(id, base);
+        if string_value.len().rem_euclid(2) != 0 {
+            return false;
+        }
+        has_repeated_pattern(&string_value, string_value.len().div_euclid(2))
+    }
+}
+
+struct RepeatedAtLeastTwice;
+
+impl IdRule for RepeatedAtLeastTwice {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        let string_value = to_digits(id, base);
+        (1..=string_value.len().div_euclid(2))
+            .any(|pattern_size| has_repeated_pattern(&string_value, pattern_size))
+    }
+}
+
+struct Palindrome;
+
+impl IdRule for Palindrome {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        let string_value = to_digits(id, base);
+        string_value.bytes().eq(string_value.bytes().rev())
+    }
+}
+
+struct StrictlyIncreasingDigits;
+
+impl IdRule for StrictlyIncreasingDigits {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        to_digits(id, base)
+            .as_bytes()
+            .windows(2)
+            .all(|pair| pair[0] < pair[1])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DigitSum {
+    Equal(u64),
+    LessThan(u64),
+    GreaterThan(u64),
+    MultipleOf(u64),
+}
+
+impl IdRule for DigitSum {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        let digit_sum: u64 = to_digits(id, base)
+            .chars()
+            .map(|digit| digit.to_digit(base).unwrap() as u64)
+            .sum();
+        match self {
+            DigitSum::Equal(value) => digit_sum == *value,
+            DigitSum::LessThan(value) => digit_sum < *value,
+            DigitSum::GreaterThan(value) => digit_sum > *value,
+            DigitSum::MultipleOf(value) => digit_sum.is_multiple_of(*value),
+        }
+    }
+}
+
+struct And(Box<dyn IdRule>, Box<dyn IdRule>);
+
+impl IdRule for And {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        self.0.is_invalid(id, base) && self.1.is_invalid(id, base)
+    }
+}
+
+struct Or(Box<dyn IdRule>, Box<dyn IdRule>);
+
+impl IdRule for Or {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        self.0.is_invalid(id, base) || self.1.is_invalid(id, base)
+    }
+}
+
+struct Not(Box<dyn IdRule>);
+
+impl IdRule for Not {
+    fn is_invalid(&self, id: Id, base: u32) -> bool {
+        !self.0.is_invalid(id, base)
+    }
+}
+
+#[cfg(any(feature = "parallel", test))]
+const CHUNK_SIZE: Id = 1 << 16;
+
+// Large ranges are split into chunks of at most `CHUNK_SIZE` ids so that they
+// can be shared between threads. Chunks are built on demand from their index.
+#[cfg(any(feature = "parallel", test))]
+fn chunk_count(range: &Range) -> Id {
+    (range.end - range.start) / CHUNK_SIZE + 1
+}
+
+#[cfg(any(feature = "parallel", test))]
+fn chunk(range: &Range, index: Id) -> RangeInclusive<Id> {
+    let start = range.start + index * CHUNK_SIZE;
+    start..=range.end.min(start.saturating_add(CHUNK_SIZE - 1))
+}
+
+fn invalid_ids_in(ids: RangeInclusive<Id>, base: u32, rule: &dyn IdRule) -> Option<Id> {
+    checked_sum(ids.filter(|id| rule.is_invalid(*id, base)).map(Some))
+}
+
+fn invalid_ids_sum(ranges: &[Range], rule: &dyn IdRule) -> Option<Id> {
+    #[cfg(feature = "parallel")]
+    let sum = ranges
+        .par_iter()
+        .filter(|range| range.start <= range.end)
+        .flat_map(|range| {
+            (0..chunk_count(range))
+                .into_par_iter()
+                .map(move |index| invalid_ids_in(chunk(range, index), range.base, rule))
+        })
+        .try_reduce(|| 0, Id::checked_add);
+    #[cfg(not(feature = "parallel"))]
+    let sum = checked_sum(
+        ranges
+            .iter()
+            .map(|range| invalid_ids_in(range.start..=range.end, range.base, rule)),
+    );
+
+    sum
+}
+
+// Parses expressions like `palindrome and not (repeated-twice or digit-sum%3)`,
+// where `not` binds tighter than `and`, which binds tighter than `or`.
+fn parse_rule(input: &str) -> Box<dyn IdRule> {
+    let tokens: Vec<String> = input
+        .replace("(", " ( ")
+        .replace(")", " ) ")
+        .split_whitespace()
+        .map(|token| token.to_owned())
+        .collect();
+
+    let mut position = 0;
+    let rule = parse_or_rule(&tokens, &mut position);
+    if position != tokens.len() {
+        panic!("Unexpected token `{}` in rule", tokens[position]);
+    }
+    rule
+}
+
+fn parse_or_rule(tokens: &[String], position: &mut usize) -> Box<dyn IdRule> {
+    let mut rule = parse_and_rule(tokens, position);
+    while tokens.get(*position).is_some_and(|token| token == "or") {
+        *position += 1;
+        rule = Box::new(Or(rule, parse_and_rule(tokens, position)));
+    }
+    rule
+}
+
+fn parse_and_rule(tokens: &[String], position: &mut usize) -> Box<dyn IdRule> {
+    let mut rule = parse_not_rule(tokens, position);
+    while tokens.get(*position).is_some_and(|token| token == "and") {
+        *position += 1;
+        rule = Box::new(And(rule, parse_not_rule(tokens, position)));
+    }
+    rule
+}
+
+fn parse_not_rule(tokens: &[String], position: &mut usize) -> Box<dyn IdRule> {
+    let token = tokens.get(*position).expect("Unexpected end of rule");
+    *position += 1;
+
+    match token.as_str() {
+        "not" => Box::new(Not(parse_not_rule(tokens, position))),
+        "(" => {
+            let rule = parse_or_rule(tokens, position);
+            if tokens.get(*position).is_none_or(|token| token != ")") {
+                panic!("Missing closing parenthesis in rule");
+            }
+            *position += 1;
+            rule
+        }
+        "repeated-twice" => Box::new(RepeatedTwice),
+        "repeated-at-least-twice" => Box::new(RepeatedAtLeastTwice),
+        "palindrome" => Box::new(Palindrome),
+        "strictly-increasing" => Box::new(StrictlyIncreasingDigits),
+        _ => Box::new(parse_digit_sum(token)),
+    }
+}
+
+fn parse_digit_sum(token: &str) -> DigitSum {
+    let condition = token
+        .strip_prefix("digit-sum")
+        .unwrap_or_else(|| panic!("Unknown rule `{token}`"));
+    let parse_value = |value: &str| value.parse().expect("Invalid digit sum value");
+
+    if let Some(value) = condition.strip_prefix("=") {
+        return DigitSum::Equal(parse_value(value));
+    }
+    if let Some(value) = condition.strip_prefix("<") {
+        return DigitSum::LessThan(parse_value(value));
+    }
+    if let Some(value) = condition.strip_prefix(">") {
+        return DigitSum::GreaterThan(parse_value(value));
+    }
+    if let Some(value) = condition.strip_prefix("%") {
+        return DigitSum::MultipleOf(parse_value(value));
+    }
+
+    panic!("Unexpected digit sum condition `{token}`")
+}
+
+#[derive(Debug, PartialEq)]
+struct RangeReport {
+    // `None` when the sum of the invalid ids overflows.
+    tally: Option<Tally>,
+    pattern_sizes: BTreeSet<u32>,
+    reversed: bool,
+    overlaps_with: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Report {
+    ranges: Vec<RangeReport>,
+    // Invalid ids that belong to several ranges, with the number of ranges.
+    double_counted: BTreeMap<Id, usize>,
+}
+
+// Every pattern size (not only the smallest one) that builds `id`.
+fn pattern_sizes(id: Id, base: u32) -> Vec<u32> {
+    let digits = digit_count(id, base);
+    (1..digits)
+        .filter(|pattern_size| {
+            digits.is_multiple_of(*pattern_size)
+                && id.is_multiple_of(repetition_multiplier(
+                    *pattern_size,
+                    digits / pattern_size,
+                    base,
+                ))
+        })
+        .collect()
+}
+
+fn range_report(ranges: &[Range], accepts_pattern_count: impl Fn(u32) -> bool) -> Report {
+    let mut reports = vec![];
+    let mut ranges_per_id: BTreeMap<Id, usize> = BTreeMap::new();
+
+    for range in ranges {
+        let ids = repeated_pattern_ids(range, &accepts_pattern_count);
+        let mut pattern_sizes_used = BTreeSet::new();
+        for id in &ids {
+            *ranges_per_id.entry(*id).or_default() += 1;
+            pattern_sizes_used.extend(pattern_sizes(*id, range.base).into_iter().filter(
+                |pattern_size| accepts_pattern_count(digit_count(*id, range.base) / pattern_size),
+            ));
+        }
+
+        reports.push(RangeReport {
+            tally: checked_sum(ids.iter().map(|id| Some(*id))).map(|sum| Tally {
+                count: ids.len() as Id,
+                sum,
+            }),
+            pattern_sizes: pattern_sizes_used,
+            reversed: range.start > range.end,
+            overlaps_with: vec![],
+        });
+    }
+
+    let mut by_start: Vec<usize> = (0..ranges.len())
+        .filter(|index| !reports[*index].reversed)
+        .collect();
+    by_start.sort_by_key(|index| ranges[*index].start);
+    for (position, index) in by_start.iter().enumerate() {
+        for other_index in &by_start[position + 1..] {
+            if ranges[*other_index].start > ranges[*index].end {
+                break;
+            }
+            reports[*index].overlaps_with.push(*other_index);
+            reports[*other_index].overlaps_with.push(*index);
+        }
+    }
+    for report in reports.iter_mut() {
+        report.overlaps_with.sort();
+    }
+
+    ranges_per_id.retain(|_, range_count| *range_count > 1);
+    Report {
+        ranges: reports,
+        double_counted: ranges_per_id,
+    }
+}
+
+fn render_range_report(ranges: &[Range], report: &Report) -> String {
+    let mut result = String::new();
+
+    for (index, (range, range_report)) in ranges.iter().zip(&report.ranges).enumerate() {
+        let tally = match range_report.tally {
+            Some(tally) => format!("{} invalid ids, sum {}", tally.count, tally.sum),
+            None => "sum of invalid ids overflows".to_owned(),
+        };
+        result += &format!(
+            "#{index} {}-{} (base {}): {tally}, pattern sizes {:?}",
+            to_digits(range.start, range.base),
+            to_digits(range.end, range.base),
+            range.base,
+            range_report.pattern_sizes,
+        );
+        if range_report.reversed {
+            result += ", REVERSED";
+        }
+        if !range_report.overlaps_with.is_empty() {
+            result += &format!(", overlaps with {:?}", range_report.overlaps_with);
+        }
+        result += "\n";
+    }
+
+    if !report.double_counted.is_empty() {
+        let extra_sum = checked_sum(
+            report
+                .double_counted
+                .iter()
+                .map(|(id, range_count)| id.checked_mul(*range_count as Id - 1)),
+        );
+        result += &format!(
+            "{} invalid ids are counted in several ranges, adding {} to the total\n",
+            report.double_counted.len(),
+            extra_sum.map_or("more than 128 bits".to_owned(), |sum| sum.to_string())
+        );
+    }
+
+    result
+}
+
+type Id = u128;
+
 #[derive(Debug)]
 struct Range {
-    start: u64,
-    end: u64,
+    start: Id,
+    end: Id,
+    base: u32,
 }
 
-fn parse_input(path: &str) -> Vec<Range> {
-    fs::read_to_string(path)
-        .unwrap()
+// Ids are decimal unless prefixed with `0b`, `0o`, `0x` or `<base>#` (e.g. `36#zz`).
+fn parse_id(value: &str) -> (Id, u32) {
+    let (digits, base) = if let Some(digits) = value.strip_prefix("0b") {
+        (digits, 2)
+    } else if let Some(digits) = value.strip_prefix("0o") {
+        (digits, 8)
+    } else if let Some(digits) = value.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some((base, digits)) = value.split_once("#") {
+        (digits, base.parse().expect("Invalid base"))
+    } else {
+        (value, 10)
+    };
+
+    if !(2..=36).contains(&base) {
+        panic!("Unsupported base {base}");
+    }
+    let id = Id::from_str_radix(digits, base).expect("Invalid id");
+    (id, base)
+}
+
+fn parse(input: &str) -> Vec<Range> {
+    input
+        .trim()
         .split(",")
         .map(|item| {
             let elements = item.split("-").collect::<Vec<_>>();
-            let start = elements[0].parse().expect("Invalid start value");
-            let end = elements[1].parse().expect("Invalid end value");
-            Range { start, end }
+            let (start, base) = parse_id(elements[0]);
+            let (end, end_base) = parse_id(elements[1]);
+            if base != end_base {
+                panic!("Range `{item}` mixes bases {base} and {end_base}");
+            }
+            Range { start, end, base }
         })
         .collect::<Vec<_>>()
 }
+
+fn parse_input(path: &str) -> Vec<Range> {
+    parse(&fs::read_to_string(path).unwrap())
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "11-22,95-115,998-1012,1188511880-1188511890,222220-222224,\
+1698522-1698528,446443-446449,38593856-38593862,565653-565659,\
+824824821-824824827,2121212118-2121212124";
+
+#[cfg(test)]
+fn brute_force_repeated_pattern_ids(
+    range: &Range,
+    accepts_pattern_count: fn(u32) -> bool,
+) -> Vec<Id> {
+    (range.start..=range.end)
+        .filter(|value| {
+            let string_value = to_digits(*value, range.base);
+            (1..string_value.len()).any(|pattern_size| {
+                string_value.len().is_multiple_of(pattern_size)
+                    && accepts_pattern_count((string_value.len() / pattern_size) as u32)
+                    && has_repeated_pattern(&string_value, pattern_size)
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_repeated_pattern_ids_example() {
+    let ranges = parse(EXAMPLE);
+    let step1: Id = ranges
+        .iter()
+        .flat_map(|range| repeated_pattern_ids(range, |pattern_count| pattern_count == 2))
+        .sum();
+    let step2: Id = ranges
+        .iter()
+        .flat_map(|range| repeated_pattern_ids(range, |pattern_count| pattern_count >= 2))
+        .sum();
+
+    assert_eq!(step1, 1227775554);
+    assert_eq!(step2, 4174379265);
+}
+
+#[test]
+fn test_repeated_pattern_ids_matches_brute_force() {
+    let accepts: [fn(u32) -> bool; 4] = [
+        |pattern_count| pattern_count == 2,
+        |pattern_count| pattern_count >= 2,
+        |pattern_count| pattern_count == 3,
+        |pattern_count| pattern_count % 2 == 0,
+    ];
+
+    for range in [
+        Range {
+            start: 1,
+            end: 200_000,
+            base: 10,
+        },
+        Range {
+            start: 1_000_000,
+            end: 1_200_000,
+            base: 10,
+        },
+        Range {
+            start: 98_765,
+            end: 123_456,
+            base: 10,
+        },
+        Range {
+            start: 1_111_111_100,
+            end: 1_111_112_000,
+            base: 10,
+        },
+    ] {
+        for accepts_pattern_count in accepts {
+            let mut ids = repeated_pattern_ids(&range, accepts_pattern_count);
+            ids.sort();
+            assert_eq!(
+                ids,
+                brute_force_repeated_pattern_ids(&range, accepts_pattern_count)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_repeated_pattern_ids_near_u64_max() {
+    let mut ids = repeated_pattern_ids(
+        &Range {
+            start: 10_000_000_000_000_000_000,
+            end: u64::MAX as Id,
+            base: 10,
+        },
+        |pattern_count| pattern_count >= 10,
+    );
+    ids.sort();
+
+    assert_eq!(ids.len(), 9);
+    assert_eq!(ids[0], 10_101_010_101_010_101_010);
+    assert_eq!(ids[1], 11_111_111_111_111_111_111);
+    assert_eq!(ids[8], 18_181_818_181_818_181_818);
+}
+
+#[cfg(test)]
+fn brute_force_smallest_repeats(value: Id, base: u32) -> u32 {
+    let string_value = to_digits(value, base);
+    (1..=string_value.len())
+        .find(|pattern_size| {
+            pattern_size == &string_value.len()
+                || has_repeated_pattern(&string_value, *pattern_size)
+        })
+        .map(|pattern_size| (string_value.len() / pattern_size) as u32)
+        .unwrap()
+}
+
+#[test]
+fn test_mobius() {
+    let values: Vec<i32> = (1..=12).map(mobius).collect();
+    assert_eq!(values, [1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0]);
+}
+
+#[test]
+fn test_repeats_tally_matches_brute_force() {
+    for range in [
+        Range {
+            start: 0,
+            end: 120_000,
+            base: 10,
+        },
+        Range {
+            start: 999_000,
+            end: 1_002_000,
+            base: 10,
+        },
+        Range {
+            start: 98_765,
+            end: 123_456,
+            base: 10,
+        },
+        Range {
+            start: 111_110,
+            end: 111_112,
+            base: 10,
+        },
+    ] {
+        for repeats in 1..=7 {
+            let matching = |accepts: &dyn Fn(u32) -> bool| {
+                (range.start..=range.end)
+                    .filter(|value| accepts(brute_force_smallest_repeats(*value, range.base)))
+                    .try_fold(Tally::default(), |tally, value| {
+                        tally.add(Tally {
+                            count: 1,
+                            sum: value,
+                        })
+                    })
+            };
+
+            assert_eq!(
+                exact_repeats_tally(&range, repeats),
+                matching(&|smallest_repeats| smallest_repeats == repeats)
+            );
+            assert_eq!(
+                at_least_repeats_tally(&range, repeats),
+                matching(&|smallest_repeats| smallest_repeats >= repeats)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_repeats_tally_counts_overlapping_periods_once() {
+    let range = Range {
+        start: 111_111,
+        end: 111_111,
+        base: 10,
+    };
+
+    assert_eq!(exact_repeats_tally(&range, 6).unwrap().count, 1);
+    assert_eq!(exact_repeats_tally(&range, 2).unwrap().count, 0);
+    assert_eq!(exact_repeats_tally(&range, 3).unwrap().count, 0);
+    assert_eq!(at_least_repeats_tally(&range, 2).unwrap().count, 1);
+}
+
+#[test]
+fn test_at_least_repeats_tally_example() {
+    let tally = parse(EXAMPLE)
+        .iter()
+        .try_fold(Tally::default(), |tally, range| {
+            tally.add(at_least_repeats_tally(range, 2)?)
+        })
+        .unwrap();
+    assert_eq!(tally.sum, 4174379265);
+}
+
+#[test]
+fn test_repeats_tally_whole_u64_range() {
+    let range = Range {
+        start: 1,
+        end: u64::MAX as Id,
+        base: 10,
+    };
+    let all = exact_repeats_tally(&range, 1)
+        .unwrap()
+        .add(at_least_repeats_tally(&range, 2).unwrap())
+        .unwrap();
+
+    assert_eq!(all.count, u64::MAX as Id);
+    assert_eq!(all.sum, u64::MAX as Id * (u64::MAX as Id + 1) / 2);
+}
+
+#[test]
+fn test_builtin_rules() {
+    assert!(RepeatedTwice.is_invalid(1212, 10));
+    assert!(!RepeatedTwice.is_invalid(121212, 10));
+    assert!(RepeatedAtLeastTwice.is_invalid(121212, 10));
+    assert!(!RepeatedAtLeastTwice.is_invalid(7, 10));
+    assert!(Palindrome.is_invalid(12321, 10));
+    assert!(!Palindrome.is_invalid(1232, 10));
+    assert!(StrictlyIncreasingDigits.is_invalid(1389, 10));
+    assert!(!StrictlyIncreasingDigits.is_invalid(1339, 10));
+    assert!(DigitSum::Equal(6).is_invalid(123, 10));
+    assert!(DigitSum::LessThan(7).is_invalid(123, 10));
+    assert!(DigitSum::GreaterThan(5).is_invalid(123, 10));
+    assert!(!DigitSum::MultipleOf(4).is_invalid(123, 10));
+}
+
+#[test]
+fn test_parse_rule_precedence() {
+    let rule = parse_rule("palindrome or repeated-twice and not digit-sum%3");
+    assert!(rule.is_invalid(1221, 10));
+    assert!(!rule.is_invalid(1212, 10));
+    assert!(rule.is_invalid(1313, 10));
+    assert!(!rule.is_invalid(1234, 10));
+
+    let rule = parse_rule("(palindrome or repeated-twice) and not digit-sum%3");
+    assert!(!rule.is_invalid(1221, 10));
+    assert!(rule.is_invalid(1313, 10));
+    assert!(rule.is_invalid(1331, 10));
+}
+
+#[test]
+fn test_rules_match_repeated_pattern_ids() {
+    let ranges = parse(EXAMPLE);
+
+    assert_eq!(
+        invalid_ids_sum(&ranges, parse_rule("repeated-twice").as_ref()),
+        Some(1227775554)
+    );
+    assert_eq!(
+        invalid_ids_sum(&ranges, parse_rule("repeated-at-least-twice").as_ref()),
+        Some(4174379265)
+    );
+}
+
+#[test]
+fn test_parse_with_base_prefixes() {
+    let ranges = parse(
+        "0xa0-0xFF,0b101-0b1111,0o17-0o77,36#zz-36#zzz,340282366920938463463374607431768211455-340282366920938463463374607431768211455",
+    );
+
+    assert_eq!(
+        ranges
+            .iter()
+            .map(|range| (range.start, range.end, range.base))
+            .collect::<Vec<_>>(),
+        [
+            (160, 255, 16),
+            (5, 15, 2),
+            (15, 63, 8),
+            (1295, 46655, 36),
+            (Id::MAX, Id::MAX, 10)
+        ]
+    );
+    assert_eq!(to_digits(46655, 36), "zzz");
+}
+
+#[test]
+fn test_repeated_pattern_ids_other_bases_match_brute_force() {
+    for range in parse("0x1-0xfffff,0b1-0b1111111111111111,0o1-0o777777,36#1-36#zzz") {
+        for accepts_pattern_count in [
+            |pattern_count| pattern_count == 2,
+            |pattern_count| pattern_count >= 2,
+        ] {
+            let mut ids = repeated_pattern_ids(&range, accepts_pattern_count);
+            ids.sort();
+            assert_eq!(
+                ids,
+                brute_force_repeated_pattern_ids(&range, accepts_pattern_count)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_hex_rules() {
+    assert!(RepeatedTwice.is_invalid(0xabab, 16));
+    assert!(!RepeatedTwice.is_invalid(0xabab, 10));
+    assert!(Palindrome.is_invalid(0b1001, 2));
+    assert!(DigitSum::Equal(30).is_invalid(0xff, 16));
+}
+
+#[test]
+fn test_repeated_pattern_ids_near_u128_max() {
+    let range = parse("0x80000000000000000000000000000000-0xffffffffffffffffffffffffffffffff")
+        .pop()
+        .unwrap();
+    let mut ids = repeated_pattern_ids(&range, |pattern_count| pattern_count == 16);
+    ids.sort();
+
+    assert_eq!(ids.len(), 128);
+    assert_eq!(ids[0], 0x80808080808080808080808080808080);
+    assert_eq!(ids[127], Id::MAX);
+}
+
+#[test]
+fn test_repeats_tally_beyond_u64() {
+    let range = parse("0x1000000000000000000000000-0xfffffffffffffffffffffffff")
+        .pop()
+        .unwrap();
+    let ids = repeated_pattern_ids(&range, |pattern_count| pattern_count >= 5);
+    let tally = at_least_repeats_tally(&range, 5).unwrap();
+
+    assert_eq!(exact_repeats_tally(&range, 25).unwrap().count, 15);
+    assert_eq!(tally.count, ids.len() as Id);
+    assert_eq!(tally.sum, ids.iter().sum());
+}
+
+#[test]
+fn test_range_report() {
+    let ranges = parse("11-22,95-115,120-90,20-100,1111-1111");
+    let report = range_report(&ranges, |pattern_count| pattern_count >= 2);
+
+    assert_eq!(
+        report.ranges[0],
+        RangeReport {
+            tally: Some(Tally { count: 2, sum: 33 }),
+            pattern_sizes: BTreeSet::from([1]),
+            reversed: false,
+            overlaps_with: vec![3],
+        }
+    );
+    assert_eq!(report.ranges[1].tally, Some(Tally { count: 2, sum: 210 }));
+    assert_eq!(report.ranges[1].overlaps_with, [3]);
+    assert!(report.ranges[2].reversed);
+    assert_eq!(report.ranges[2].tally, Some(Tally::default()));
+    assert_eq!(report.ranges[2].overlaps_with, []);
+    assert_eq!(report.ranges[3].overlaps_with, [0, 1]);
+    assert_eq!(report.ranges[4].pattern_sizes, BTreeSet::from([1, 2]));
+    assert_eq!(report.double_counted, BTreeMap::from([(22, 2), (99, 2)]));
+}
+
+#[test]
+fn test_chunked_sums_match_sequential() {
+    let ranges = parse("1-300000,250000-250100,500-400,0xfff0-0x2ffff,36#zz-36#zzz");
+    assert_eq!(chunk_count(&ranges[0]), 5);
+    for range in ranges.iter().filter(|range| range.start <= range.end) {
+        let ids: Vec<Id> = (0..chunk_count(range))
+            .flat_map(|index| chunk(range, index))
+            .collect();
+        assert_eq!(ids, (range.start..=range.end).collect::<Vec<_>>());
+    }
+    let widest = parse("0x0-0xffffffffffffffffffffffffffffffff")
+        .pop()
+        .unwrap();
+    assert_eq!(chunk_count(&widest), 1 << 112);
+    assert_eq!(chunk(&widest, (1 << 112) - 1), Id::MAX - 0xffff..=Id::MAX);
+
+    for rule in ["repeated-at-least-twice", "palindrome or digit-sum%7"] {
+        let rule = parse_rule(rule);
+        let sequential: Id = ranges
+            .iter()
+            .flat_map(|range| {
+                (range.start..=range.end).filter(|id| rule.is_invalid(*id, range.base))
+            })
+            .sum();
+        assert_eq!(invalid_ids_sum(&ranges, rule.as_ref()), Some(sequential));
+    }
+
+    let sequential: Id = ranges
+        .iter()
+        .flat_map(|range| repeated_pattern_ids(range, |pattern_count| pattern_count >= 2))
+        .sum();
+    assert_eq!(
+        repeated_pattern_ids_sum(&ranges, |pattern_count| pattern_count >= 2),
+        Some(sequential)
+    );
+}
+
+#[test]
+fn test_sums_near_u128_max() {
+    let high = parse("0x80000000000000000000000000000000-0xffffffffffffffffffffffffffffffff");
+    assert_eq!(
+        repeated_pattern_ids_sum(&high, |pattern_count| pattern_count == 16),
+        None
+    );
+    assert_eq!(at_least_repeats_tally(&high[0], 16), None);
+    assert_eq!(
+        range_report(&high, |pattern_count| pattern_count == 16).ranges[0].tally,
+        None
+    );
+
+    let range = parse("0x7e7e7e7e7e7e7e7e7e7e7e7e7e7e7e7e-0x7fffffffffffffffffffffffffffffff");
+    let expected = Tally {
+        count: 2,
+        sum: 0xfdfdfdfdfdfdfdfdfdfdfdfdfdfdfdfd,
+    };
+    assert_eq!(
+        repeated_pattern_ids_sum(&range, |pattern_count| pattern_count == 16),
+        Some(expected.sum)
+    );
+    assert_eq!(exact_repeats_tally(&range[0], 16), Some(expected));
+    assert_eq!(
+        range_report(&range, |pattern_count| pattern_count == 16).ranges[0].tally,
+        Some(expected)
+    );
+}
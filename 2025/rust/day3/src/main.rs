@@ -1,37 +1,543 @@
-use std::fs;
+use std::{cmp::Ordering, env, fmt, fs, iter::Sum, ops::Add};
 
 fn main() {
-    let banks = parse_input("./src/input");
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let base = match args.iter().position(|arg| arg == "--base") {
+        Some(index) => {
+            let base = args
+                .get(index + 1)
+                .expect("Missing base")
+                .parse()
+                .expect("Invalid base");
+            args.drain(index..=index + 1);
+            base
+        }
+        None => 10,
+    };
+    let banks = parse_input("./src/input", base);
+
+    match args.first().map(|mode| mode.as_str()) {
+        Some("show") => {
+            let number_of_battery = args
+                .get(1)
+                .map(|value| value.parse().expect("Invalid number of batteries"))
+                .unwrap_or(12);
+            for bank in &banks {
+                println!(
+                    "{}",
+                    render_selection(bank, &selected_batteries(bank, number_of_battery), base)
+                );
+            }
+            return;
+        }
+        Some("select") => {
+            let number_of_battery = args
+                .get(1)
+                .expect("Missing number of batteries")
+                .parse()
+                .expect("Invalid number of batteries");
+            let mode = parse_selection_mode(&args[2..]);
 
-    let low_joltage: u64 = banks.iter().map(|bank| joltage(bank, 2)).sum();
+            let mut total_joltage = Joltage::default();
+            for bank in &banks {
+                match select_batteries_with(bank, number_of_battery, mode) {
+                    Some(positions) => {
+                        total_joltage = total_joltage
+                            + Joltage::from_digits(
+                                positions.iter().map(|position| bank[*position]),
+                                base,
+                            );
+                        println!(
+                            "{}",
+                            render_selection(bank, &batteries_at(bank, &positions), base)
+                        );
+                    }
+                    None => println!("{} no valid selection", render_selection(bank, &[], base)),
+                }
+            }
+            dbg!(total_joltage);
+            return;
+        }
+        Some("budget") => {
+            let values: Vec<usize> = args[1..]
+                .iter()
+                .map(|value| value.parse().expect("Invalid budget value"))
+                .collect();
+            let [budget, min_picks, max_picks] = values[..] else {
+                panic!("Expected a budget, a minimum and a maximum number of batteries per bank");
+            };
+
+            let allocation = allocate_budget(&banks, budget, min_picks, max_picks, base)
+                .expect("The budget can't satisfy the minimum number of batteries per bank");
+            for (bank, number_of_battery) in banks.iter().zip(&allocation.picks) {
+                println!(
+                    "{number_of_battery:>3} {}",
+                    render_selection(bank, &selected_batteries(bank, *number_of_battery), base)
+                );
+            }
+            dbg!(allocation.total);
+            return;
+        }
+        _ => {}
+    }
+
+    let low_joltage: Joltage = banks.iter().map(|bank| joltage(bank, 2, base)).sum();
     dbg!(low_joltage); // 17694
 
-    let high_joltage: u64 = banks.iter().map(|bank| joltage(bank, 12)).sum();
+    let high_joltage: Joltage = banks.iter().map(|bank| joltage(bank, 12, base)).sum();
 
     dbg!(high_joltage); // 175659236361660
 }
 
-fn joltage(bank: &Bank, number_of_battery: u32) -> u64 {
-    let mut result: u64 = 0;
-    let mut start: usize = 0;
+// Digits of a joltage in `base`, most significant first and without leading
+// zeros, so that any number of batteries can be selected and summed.
+#[derive(Clone, Default)]
+struct Joltage {
+    digits: Vec<u8>,
+    base: u32,
+}
 
-    for number_of_battery in (0..=number_of_battery - 1).rev() {
-        let end = bank.len() - number_of_battery as usize;
-        let max = find_max_with_position(&bank[start..end]).unwrap();
-        result += max.0 * 10_u64.pow(number_of_battery);
-        start += max.1 + 1;
+impl Joltage {
+    fn from_digits(digits: impl Iterator<Item = u64>, base: u32) -> Joltage {
+        Joltage {
+            digits: digits
+                .map(|digit| digit as u8)
+                .skip_while(|digit| *digit == 0)
+                .collect(),
+            base,
+        }
     }
+}
 
-    result
+impl Add for Joltage {
+    type Output = Joltage;
+
+    fn add(self, other: Joltage) -> Joltage {
+        // A zero joltage, like the default one, takes the base of the other side.
+        let base = if self.digits.is_empty() {
+            other.base
+        } else {
+            self.base
+        };
+        if !other.digits.is_empty() && other.base != base {
+            panic!("Can't add joltages in base {base} and {}", other.base);
+        }
+
+        let mut digits = vec![];
+        let mut carry = 0;
+        let mut left = self.digits.iter().rev();
+        let mut right = other.digits.iter().rev();
+
+        loop {
+            let (left_digit, right_digit) = (left.next(), right.next());
+            if left_digit.is_none() && right_digit.is_none() && carry == 0 {
+                break;
+            }
+            let sum = *left_digit.unwrap_or(&0) as u32 + *right_digit.unwrap_or(&0) as u32 + carry;
+            digits.push((sum % base) as u8);
+            carry = sum / base;
+        }
+
+        digits.reverse();
+        Joltage { digits, base }
+    }
+}
+
+impl Sum for Joltage {
+    fn sum<I: Iterator<Item = Joltage>>(iter: I) -> Joltage {
+        iter.fold(Joltage::default(), Joltage::add)
+    }
+}
+
+impl fmt::Display for Joltage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        for digit in &self.digits {
+            write!(f, "{}", char::from_digit(*digit as u32, self.base).unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Joltage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+// Joltages are compared by their digits, which only makes sense in a same base.
+impl PartialEq for Joltage {
+    fn eq(&self, other: &Joltage) -> bool {
+        self.digits == other.digits
+    }
+}
+
+impl Eq for Joltage {}
+
+impl Ord for Joltage {
+    fn cmp(&self, other: &Joltage) -> Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.cmp(&other.digits))
+    }
+}
+
+impl PartialOrd for Joltage {
+    fn partial_cmp(&self, other: &Joltage) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn joltage(bank: &Bank, number_of_battery: u32, base: u32) -> Joltage {
+    Joltage::from_digits(
+        select_batteries(bank, number_of_battery as usize)
+            .into_iter()
+            .map(|position| bank[position]),
+        base,
+    )
+}
+
+fn select_batteries(bank: &Bank, number_of_battery: usize) -> Vec<usize> {
+    stack_selection(bank, number_of_battery, Objective::Maximize)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Objective {
+    #[default]
+    Maximize,
+    Minimize,
+}
+
+impl Objective {
+    fn prefers(&self, value: u64, other: u64) -> bool {
+        match self {
+            Objective::Maximize => value > other,
+            Objective::Minimize => value < other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Constraint {
+    #[default]
+    None,
+    // Selected positions are at least this far apart.
+    MinGap(usize),
+    // Two consecutive selected batteries never have the same joltage.
+    NoIdenticalAdjacent,
+    // At most `max` batteries are selected among any `window` consecutive ones.
+    WindowCap {
+        window: usize,
+        max: usize,
+    },
+}
+
+impl Constraint {
+    fn allows(&self, bank: &Bank, selected: &[usize], position: usize) -> bool {
+        match self {
+            Constraint::None => true,
+            Constraint::MinGap(gap) => selected.last().is_none_or(|last| position >= last + gap),
+            Constraint::NoIdenticalAdjacent => selected
+                .last()
+                .is_none_or(|last| bank[*last] != bank[position]),
+            Constraint::WindowCap { window, max } => {
+                selected
+                    .iter()
+                    .rev()
+                    .take_while(|selected_position| **selected_position + window > position)
+                    .count()
+                    < *max
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct SelectionMode {
+    objective: Objective,
+    constraint: Constraint,
+}
+
+// Parses arguments like `min gap=3`, `no-identical-adjacent` or `max window=5:2`.
+fn parse_selection_mode(args: &[String]) -> SelectionMode {
+    let mut mode = SelectionMode::default();
+    let parse_value = |value: &str| value.parse().expect("Invalid constraint value");
+
+    for arg in args {
+        if arg == "max" {
+            mode.objective = Objective::Maximize;
+        } else if arg == "min" {
+            mode.objective = Objective::Minimize;
+        } else if arg == "no-identical-adjacent" {
+            mode.constraint = Constraint::NoIdenticalAdjacent;
+        } else if let Some(gap) = arg.strip_prefix("gap=") {
+            mode.constraint = Constraint::MinGap(parse_value(gap));
+        } else if let Some((window, max)) = arg
+            .strip_prefix("window=")
+            .and_then(|value| value.split_once(":"))
+        {
+            mode.constraint = Constraint::WindowCap {
+                window: parse_value(window),
+                max: parse_value(max),
+            };
+        } else {
+            panic!("Unexpected selection mode `{arg}`");
+        }
+    }
+
+    mode
+}
+
+// Positions of the `number_of_battery` batteries forming the best number, or
+// `None` when the constraint can't be satisfied.
+fn select_batteries_with(
+    bank: &Bank,
+    number_of_battery: usize,
+    mode: SelectionMode,
+) -> Option<Vec<usize>> {
+    if number_of_battery > bank.len() {
+        return None;
+    }
+
+    match mode.constraint {
+        Constraint::None => Some(stack_selection(bank, number_of_battery, mode.objective)),
+        _ => constrained_selection(bank, number_of_battery, mode),
+    }
+}
+
+// A battery is dropped from the stack whenever a preferred one comes after it
+// and enough batteries remain to still pick `number_of_battery` of them.
+fn stack_selection(bank: &Bank, number_of_battery: usize, objective: Objective) -> Vec<usize> {
+    let mut droppable = bank.len() - number_of_battery;
+    let mut selected: Vec<usize> = Vec::with_capacity(bank.len());
+
+    for (position, value) in bank.iter().enumerate() {
+        while droppable > 0
+            && selected
+                .last()
+                .is_some_and(|last| objective.prefers(*value, bank[*last]))
+        {
+            selected.pop();
+            droppable -= 1;
+        }
+        selected.push(position);
+    }
+
+    selected.truncate(number_of_battery);
+    selected
+}
+
+// Picks the digits one by one: each time the preferred joltage among the
+// positions after which enough batteries can still be selected, at its
+// earliest position since it never leaves fewer options for the next picks.
+fn constrained_selection(
+    bank: &Bank,
+    number_of_battery: usize,
+    mode: SelectionMode,
+) -> Option<Vec<usize>> {
+    // `no_identical_adjacent_picks[i][joltage]`: the most batteries that can be
+    // selected from position `i` when the previous one has this joltage.
+    let mut no_identical_adjacent_picks = vec![];
+    if mode.constraint == Constraint::NoIdenticalAdjacent {
+        let width = *bank.iter().max().unwrap_or(&0) as usize + 1;
+        no_identical_adjacent_picks = vec![vec![0; width]; bank.len() + 1];
+        for position in (0..bank.len()).rev() {
+            for previous in 0..width {
+                let skipped = no_identical_adjacent_picks[position + 1][previous];
+                no_identical_adjacent_picks[position][previous] = if bank[position] as usize
+                    == previous
+                {
+                    skipped
+                } else {
+                    skipped
+                        .max(1 + no_identical_adjacent_picks[position + 1][bank[position] as usize])
+                };
+            }
+        }
+    }
+
+    let max_picks_after = |selected: &[usize]| -> usize {
+        let last = selected[selected.len() - 1];
+        match mode.constraint {
+            Constraint::None => bank.len() - last - 1,
+            // Consecutive picks are always at least one position apart.
+            Constraint::MinGap(gap) => {
+                let gap = gap.max(1);
+                bank.len().saturating_sub(last + gap).div_ceil(gap)
+            }
+            Constraint::NoIdenticalAdjacent => {
+                no_identical_adjacent_picks[last + 1][bank[last] as usize]
+            }
+            // Selecting every allowed battery as early as possible is optimal.
+            Constraint::WindowCap { .. } => {
+                let mut picks = selected.to_vec();
+                for position in last + 1..bank.len() {
+                    if mode.constraint.allows(bank, &picks, position) {
+                        picks.push(position);
+                    }
+                }
+                picks.len() - selected.len()
+            }
+        }
+    };
+
+    let mut selected: Vec<usize> = vec![];
+    for remaining in (0..number_of_battery).rev() {
+        let start = selected.last().map_or(0, |last| last + 1);
+        let mut best: Option<usize> = None;
+
+        for position in start..bank.len() {
+            if best.is_some_and(|best| !mode.objective.prefers(bank[position], bank[best])) {
+                continue;
+            }
+            if !mode.constraint.allows(bank, &selected, position) {
+                continue;
+            }
+            selected.push(position);
+            if max_picks_after(&selected) >= remaining {
+                best = Some(position);
+            }
+            selected.pop();
+        }
+
+        selected.push(best?);
+    }
+
+    Some(selected)
+}
+
+#[derive(Debug, PartialEq)]
+struct Allocation {
+    // Number of batteries selected in each bank.
+    picks: Vec<usize>,
+    total: Joltage,
+}
+
+// Shares at most `budget` batteries between the banks, each one getting between
+// `min_picks` and `max_picks` of them, so that the summed joltage is maximal.
+// This is a knapsack over the banks where `best[used]` is the best total using
+// `used` batteries in the banks seen so far.
+fn allocate_budget(
+    banks: &[Bank],
+    budget: usize,
+    min_picks: usize,
+    max_picks: usize,
+    base: u32,
+) -> Option<Allocation> {
+    let mut best: Vec<Option<Joltage>> = vec![None; budget + 1];
+    best[0] = Some(Joltage::from_digits(std::iter::empty(), base));
+    let mut choices: Vec<Vec<usize>> = vec![];
+
+    for bank in banks {
+        let joltages: Vec<(usize, Joltage)> = (min_picks..=max_picks.min(bank.len()))
+            .map(|number_of_battery| {
+                (
+                    number_of_battery,
+                    joltage(bank, number_of_battery as u32, base),
+                )
+            })
+            .collect();
+
+        let mut next_best: Vec<Option<Joltage>> = vec![None; budget + 1];
+        let mut bank_choices = vec![0; budget + 1];
+        for (used, total) in best.iter().enumerate() {
+            let Some(total) = total else {
+                continue;
+            };
+            for (number_of_battery, joltage) in &joltages {
+                let next_used = used + number_of_battery;
+                if next_used > budget {
+                    break;
+                }
+                let candidate = total.clone() + joltage.clone();
+                if next_best[next_used]
+                    .as_ref()
+                    .is_none_or(|current| candidate > *current)
+                {
+                    next_best[next_used] = Some(candidate);
+                    bank_choices[next_used] = *number_of_battery;
+                }
+            }
+        }
+
+        best = next_best;
+        choices.push(bank_choices);
+    }
+
+    let (mut used, total) = best
+        .into_iter()
+        .enumerate()
+        .filter_map(|(used, total)| total.map(|total| (used, total)))
+        .max_by(|left, right| left.1.cmp(&right.1).then(right.0.cmp(&left.0)))?;
+
+    let mut picks = vec![0; banks.len()];
+    for (index, bank_choices) in choices.iter().enumerate().rev() {
+        picks[index] = bank_choices[used];
+        used -= picks[index];
+    }
+
+    Some(Allocation { picks, total })
 }
 
-fn parse_bank(input: &str) -> Bank {
+#[derive(Debug, PartialEq)]
+struct Battery {
+    position: usize,
+    joltage: u64,
+}
+
+fn batteries_at(bank: &Bank, positions: &[usize]) -> Vec<Battery> {
+    positions
+        .iter()
+        .map(|position| Battery {
+            position: *position,
+            joltage: bank[*position],
+        })
+        .collect()
+}
+
+fn selected_batteries(bank: &Bank, number_of_battery: usize) -> Vec<Battery> {
+    batteries_at(bank, &select_batteries(bank, number_of_battery))
+}
+
+// Prints the bank with the selected batteries in bold green, followed by their
+// positions.
+fn render_selection(bank: &Bank, selection: &[Battery], base: u32) -> String {
+    let mut selected = selection.iter().map(|battery| battery.position).peekable();
+    let mut result = String::new();
+
+    for (position, value) in bank.iter().enumerate() {
+        let digit = char::from_digit(*value as u32, base).unwrap();
+        if selected.next_if_eq(&position).is_some() {
+            result += &format!("\x1b[1;32m{digit}\x1b[0m");
+        } else {
+            result.push(digit);
+        }
+    }
+
+    let positions: Vec<usize> = selection.iter().map(|battery| battery.position).collect();
+    result + &format!(" {positions:?}")
+}
+
+fn parse_bank(input: &str, base: u32) -> Bank {
+    if !(2..=36).contains(&base) {
+        panic!("Unsupported base {base}");
+    }
+
     input
-        .bytes()
-        .map(|value| (value - 48) as u64)
+        .chars()
+        .map(|value| {
+            value
+                .to_digit(base)
+                .unwrap_or_else(|| panic!("Invalid digit `{value}` for base {base}"))
+                as u64
+        })
         .collect::<Bank>()
 }
 
+#[cfg(test)]
 fn find_max_with_position(values: &[u64]) -> Option<(u64, usize)> {
     if values.is_empty() {
         return None;
@@ -51,10 +557,347 @@ fn find_max_with_position(values: &[u64]) -> Option<(u64, usize)> {
 
 type Bank = Vec<u64>;
 
-fn parse_input(path: &str) -> Vec<Bank> {
+fn parse_input(path: &str, base: u32) -> Vec<Bank> {
     fs::read_to_string(path)
         .unwrap()
         .lines()
-        .map(parse_bank)
+        .map(|line| parse_bank(line, base))
         .collect()
 }
+
+#[cfg(test)]
+const EXAMPLE: &str = "987654321111111
+811111111111119
+234234234234278
+818181911112111";
+
+#[cfg(test)]
+fn greedy_joltage(bank: &Bank, number_of_battery: u32) -> u64 {
+    let mut result: u64 = 0;
+    let mut start: usize = 0;
+
+    for number_of_battery in (0..=number_of_battery - 1).rev() {
+        let end = bank.len() - number_of_battery as usize;
+        let max = find_max_with_position(&bank[start..end]).unwrap();
+        result += max.0 * 10_u64.pow(number_of_battery);
+        start += max.1 + 1;
+    }
+
+    result
+}
+
+#[cfg(test)]
+fn random_bank(seed: u64, length: usize) -> Bank {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % 9 + 1
+        })
+        .collect()
+}
+
+#[test]
+fn test_joltage_example() {
+    let banks: Vec<Bank> = EXAMPLE.lines().map(|line| parse_bank(line, 10)).collect();
+
+    assert_eq!(
+        banks
+            .iter()
+            .map(|bank| joltage(bank, 2, 10))
+            .sum::<Joltage>()
+            .to_string(),
+        "357"
+    );
+    assert_eq!(
+        banks
+            .iter()
+            .map(|bank| joltage(bank, 12, 10))
+            .sum::<Joltage>()
+            .to_string(),
+        "3121910778619"
+    );
+}
+
+#[test]
+fn test_joltage_matches_greedy() {
+    for seed in 0..200 {
+        let bank = random_bank(seed, 12 + seed as usize % 50);
+        for number_of_battery in [1, 2, 12] {
+            assert_eq!(
+                joltage(&bank, number_of_battery, 10).to_string(),
+                greedy_joltage(&bank, number_of_battery).to_string()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_select_batteries_large_bank() {
+    let bank = random_bank(42, 2_000_000);
+    let selected = select_batteries(&bank, 1_000_000);
+
+    assert_eq!(selected.len(), 1_000_000);
+    assert!(selected.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn test_selected_batteries() {
+    let bank = parse_bank("818181911112111", 10);
+
+    assert_eq!(
+        selected_batteries(&bank, 2),
+        [
+            Battery {
+                position: 6,
+                joltage: 9
+            },
+            Battery {
+                position: 11,
+                joltage: 2
+            }
+        ]
+    );
+}
+
+#[test]
+fn test_render_selection() {
+    let bank = parse_bank("8191", 10);
+
+    assert_eq!(
+        render_selection(&bank, &selected_batteries(&bank, 2), 10),
+        "81\x1b[1;32m9\x1b[0m\x1b[1;32m1\x1b[0m [2, 3]"
+    );
+}
+
+#[test]
+fn test_joltage_beyond_u64() {
+    let banks: Vec<Bank> = [
+        "1234567890123456789012345678901234567890",
+        "9999999999999999999999999999999999999999",
+    ]
+    .into_iter()
+    .map(|line| parse_bank(line, 10))
+    .collect();
+
+    assert_eq!(
+        joltage(&banks[0], 30, 10).to_string(),
+        "923456789012345678901234567890"
+    );
+    assert_eq!(
+        banks
+            .iter()
+            .map(|bank| joltage(bank, 30, 10))
+            .sum::<Joltage>()
+            .to_string(),
+        "1923456789012345678901234567889"
+    );
+}
+
+#[test]
+fn test_joltage_add() {
+    let joltage = |value: &str| Joltage::from_digits(parse_bank(value, 10).into_iter(), 10);
+
+    assert_eq!((joltage("0") + joltage("0")).to_string(), "0");
+    assert_eq!((joltage("999") + joltage("1")).to_string(), "1000");
+    assert_eq!((joltage("007") + joltage("35")).to_string(), "42");
+}
+
+#[cfg(test)]
+fn brute_force_selection(
+    bank: &Bank,
+    number_of_battery: usize,
+    mode: SelectionMode,
+) -> Option<Vec<u64>> {
+    let mut best: Option<Vec<u64>> = None;
+
+    for mask in 0_u32..1 << bank.len() {
+        if mask.count_ones() as usize != number_of_battery {
+            continue;
+        }
+        let positions: Vec<usize> = (0..bank.len())
+            .filter(|position| mask & (1 << position) != 0)
+            .collect();
+        let valid = (0..positions.len()).all(|index| {
+            mode.constraint
+                .allows(bank, &positions[..index], positions[index])
+        });
+        if !valid {
+            continue;
+        }
+
+        let digits: Vec<u64> = positions.iter().map(|position| bank[*position]).collect();
+        let is_better = best.as_ref().is_none_or(|best| match mode.objective {
+            Objective::Maximize => digits > *best,
+            Objective::Minimize => digits < *best,
+        });
+        if is_better {
+            best = Some(digits);
+        }
+    }
+
+    best
+}
+
+#[test]
+fn test_constrained_selection_matches_brute_force() {
+    let constraints = [
+        Constraint::None,
+        Constraint::MinGap(0),
+        Constraint::MinGap(1),
+        Constraint::MinGap(2),
+        Constraint::MinGap(3),
+        Constraint::NoIdenticalAdjacent,
+        Constraint::WindowCap { window: 3, max: 1 },
+        Constraint::WindowCap { window: 4, max: 2 },
+    ];
+
+    for seed in 0..60 {
+        let bank: Bank = random_bank(seed, 6 + seed as usize % 7)
+            .into_iter()
+            .map(|value| value % 4)
+            .collect();
+        for constraint in constraints {
+            for objective in [Objective::Maximize, Objective::Minimize] {
+                let mode = SelectionMode {
+                    objective,
+                    constraint,
+                };
+                for number_of_battery in 1..=5 {
+                    let selected = select_batteries_with(&bank, number_of_battery, mode);
+                    assert_eq!(
+                        selected.map(|positions| positions
+                            .iter()
+                            .map(|position| bank[*position])
+                            .collect::<Vec<_>>()),
+                        brute_force_selection(&bank, number_of_battery, mode),
+                        "{bank:?} {mode:?} {number_of_battery}"
+                    );
+                }
+            }
+        }
+    }
+
+    let mode = SelectionMode {
+        objective: Objective::Maximize,
+        constraint: Constraint::MinGap(0),
+    };
+    assert_eq!(
+        select_batteries_with(&parse_bank("19", 10), 2, mode),
+        Some(vec![0, 1])
+    );
+}
+
+#[test]
+fn test_parse_selection_mode() {
+    let args =
+        |values: &[&str]| -> Vec<String> { values.iter().map(|value| value.to_string()).collect() };
+
+    assert_eq!(
+        parse_selection_mode(&args(&["min", "window=5:2"])),
+        SelectionMode {
+            objective: Objective::Minimize,
+            constraint: Constraint::WindowCap { window: 5, max: 2 },
+        }
+    );
+    assert_eq!(
+        parse_selection_mode(&args(&["gap=3"])),
+        SelectionMode {
+            objective: Objective::Maximize,
+            constraint: Constraint::MinGap(3),
+        }
+    );
+}
+
+#[test]
+fn test_allocate_budget_matches_brute_force() {
+    let banks: Vec<Bank> = (0..3)
+        .map(|seed| {
+            random_bank(seed, 8)
+                .into_iter()
+                .map(|value| value % 5)
+                .collect()
+        })
+        .collect();
+
+    for budget in 0..=20 {
+        for (min_picks, max_picks) in [(0, 8), (1, 4), (2, 3), (3, 9)] {
+            let mut expected: Option<Joltage> = None;
+            for first in min_picks..=max_picks.min(8) {
+                for second in min_picks..=max_picks.min(8) {
+                    for third in min_picks..=max_picks.min(8) {
+                        if first + second + third > budget {
+                            continue;
+                        }
+                        let total = joltage(&banks[0], first as u32, 10)
+                            + joltage(&banks[1], second as u32, 10)
+                            + joltage(&banks[2], third as u32, 10);
+                        if expected.as_ref().is_none_or(|expected| total > *expected) {
+                            expected = Some(total);
+                        }
+                    }
+                }
+            }
+
+            let allocation = allocate_budget(&banks, budget, min_picks, max_picks, 10);
+            assert_eq!(
+                allocation
+                    .as_ref()
+                    .map(|allocation| allocation.total.clone()),
+                expected
+            );
+            if let Some(allocation) = allocation {
+                assert!(allocation.picks.iter().sum::<usize>() <= budget);
+                assert_eq!(
+                    allocation
+                        .picks
+                        .iter()
+                        .zip(&banks)
+                        .map(|(picks, bank)| joltage(bank, *picks as u32, 10))
+                        .sum::<Joltage>(),
+                    allocation.total
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_joltage_ordering() {
+    let joltage = |value: &str| Joltage::from_digits(parse_bank(value, 10).into_iter(), 10);
+
+    assert!(joltage("99") < joltage("100"));
+    assert!(joltage("0") < joltage("1"));
+    assert!(joltage("123") > joltage("122"));
+}
+
+#[test]
+fn test_hex_banks() {
+    let banks: Vec<Bank> = ["a3f9", "1ff0"]
+        .into_iter()
+        .map(|line| parse_bank(line, 16))
+        .collect();
+
+    assert_eq!(banks[0], [10, 3, 15, 9]);
+    assert_eq!(joltage(&banks[0], 2, 16).to_string(), "f9");
+    assert_eq!(
+        banks
+            .iter()
+            .map(|bank| joltage(bank, 3, 16))
+            .sum::<Joltage>()
+            .to_string(),
+        "1ae9"
+    );
+    assert_eq!(
+        render_selection(&banks[0], &selected_batteries(&banks[0], 1), 16),
+        "a3\x1b[1;32mf\x1b[0m9 [2]"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid digit `g` for base 16")]
+fn test_parse_bank_rejects_invalid_digit() {
+    parse_bank("a3g9", 16);
+}
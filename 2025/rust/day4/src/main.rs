@@ -1,110 +1,825 @@
-use std::{collections::HashMap, fs};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+};
 
 fn main() {
-    let mut map = parse_input("src/input");
-    let removed_rolls = map.remove_accessible_rolls();
-    dbg!(removed_rolls); // 1540
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let rules = parse_rules(&mut args);
+    let sparse = take_switch(&mut args, "--sparse");
+    let mut map = parse_input("src/input", rules, sparse);
 
-    let mut total_removed_rolls = removed_rolls;
-    loop {
-        let removed_rolls = map.remove_accessible_rolls();
-        total_removed_rolls += removed_rolls;
-        if removed_rolls == 0 {
-            break;
+    match args.first().map(|mode| mode.as_str()) {
+        Some("rounds") => {
+            let initial_rolls = map.rolls().len();
+            let removal_rounds = removal_rounds(&map.remove_rolls());
+            let colour = args.iter().any(|arg| arg == "--colour");
+            if sparse {
+                print!("{}", render_sparse_removal_rounds(&map, &removal_rounds));
+            } else {
+                print!("{}", render_removal_rounds(&map, &removal_rounds, colour));
+            }
+            print!("{}", render_round_counts(initial_rolls, &removal_rounds));
+            return;
+        }
+        Some("cores") => {
+            print!(
+                "{}",
+                render_survivors(&survivors_by_threshold(&map.core_numbers()))
+            );
+            return;
+        }
+        Some("components") => {
+            let initial_rolls = map.rolls();
+            let removal_rounds = removal_rounds(&map.remove_rolls());
+            let grids = args.iter().any(|arg| arg == "--grids");
+            for (title, round) in [
+                ("after the first round", 1),
+                ("at the fixed point", usize::MAX),
+            ] {
+                let survivors = survivors_after(&initial_rolls, &removal_rounds, round);
+                let components = components(&survivors);
+                println!("{} components {title}", components.len());
+                print!("{}", render_components(&components, &map.rules, grids));
+            }
+            return;
         }
+        Some("automaton") => {
+            let max_steps = take_flag(&mut args, "--steps")
+                .map(|value| value.parse().expect("Invalid number of steps"))
+                .unwrap_or(1000);
+            let rule: Box<dyn CellRule> = match args.get(1).map(|rule| rule.as_str()) {
+                None | Some("removal") => Box::new(RollRemoval::from(&map.rules)),
+                Some(rule) => Box::new(LifeLike::parse(rule, &map.rules)),
+            };
+            let outcome = map.run(rule.as_ref(), max_steps);
+            print!("{}", map.render());
+            dbg!(outcome);
+            return;
+        }
+        _ => {}
     }
 
+    let removed_rolls_by_round = map.remove_rolls_by_round();
+
+    let removed_rolls = removed_rolls_by_round.first().copied().unwrap_or(0);
+    dbg!(removed_rolls); // 1540
+
+    let total_removed_rolls: usize = removed_rolls_by_round.iter().sum();
     dbg!(total_removed_rolls); // 8972
 }
 
+#[derive(Debug, Clone, PartialEq)]
+enum Neighbourhood {
+    VonNeumann,
+    Moore,
+    // Axial coordinates: each row is shifted half a cell from the previous one.
+    Hexagonal,
+    Custom(Vec<(i64, i64)>),
+}
+
+impl Neighbourhood {
+    fn offsets(&self) -> Vec<(i64, i64)> {
+        match self {
+            Neighbourhood::VonNeumann => vec![(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighbourhood::Moore => vec![
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+            Neighbourhood::Hexagonal => vec![(0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)],
+            Neighbourhood::Custom(offsets) => offsets.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Topology {
+    // Cells outside of the map are empty.
+    Bounded,
+    // Neighbours wrap across the edges of the map's bounding box.
+    Toroidal,
+}
+
+// A roll is accessible when fewer than `threshold` of its neighbours are rolls.
+#[derive(Debug, Clone)]
+struct Rules {
+    neighbourhood: Neighbourhood,
+    topology: Topology,
+    threshold: usize,
+    roll: char,
+    empty: char,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            neighbourhood: Neighbourhood::Moore,
+            topology: Topology::Bounded,
+            threshold: 4,
+            roll: '@',
+            empty: '.',
+        }
+    }
+}
+
+fn parse_neighbourhood(value: &str) -> Neighbourhood {
+    match value {
+        "4" | "von-neumann" => Neighbourhood::VonNeumann,
+        "8" | "moore" => Neighbourhood::Moore,
+        "hex" => Neighbourhood::Hexagonal,
+        _ => Neighbourhood::Custom(
+            value
+                .split(';')
+                .map(|offset| {
+                    let (dx, dy) = offset
+                        .split_once(',')
+                        .unwrap_or_else(|| panic!("Invalid offset `{offset}`"));
+                    (
+                        dx.trim().parse().expect("Invalid x offset"),
+                        dy.trim().parse().expect("Invalid y offset"),
+                    )
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    let value = args
+        .get(index + 1)
+        .unwrap_or_else(|| panic!("Missing value for {flag}"))
+        .clone();
+    args.drain(index..=index + 1);
+    Some(value)
+}
+
+fn take_switch(args: &mut Vec<String>, switch: &str) -> bool {
+    let index = args.iter().position(|arg| arg == switch);
+    if let Some(index) = index {
+        args.remove(index);
+    }
+    index.is_some()
+}
+
+fn parse_glyph(value: &str) -> char {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(glyph), None) => glyph,
+        _ => panic!("Invalid glyph `{value}`"),
+    }
+}
+
+// Removes `--neighbourhood`, `--topology`, `--threshold`, `--roll` and
+// `--empty` from the arguments and returns the rules they describe.
+fn parse_rules(args: &mut Vec<String>) -> Rules {
+    let mut rules = Rules::default();
+    if let Some(value) = take_flag(args, "--neighbourhood") {
+        rules.neighbourhood = parse_neighbourhood(&value);
+    }
+    if let Some(value) = take_flag(args, "--topology") {
+        rules.topology = match value.as_str() {
+            "bounded" => Topology::Bounded,
+            "toroidal" => Topology::Toroidal,
+            _ => panic!("Invalid topology `{value}`"),
+        };
+    }
+    if let Some(value) = take_flag(args, "--threshold") {
+        rules.threshold = value.parse().expect("Invalid threshold");
+    }
+    if let Some(value) = take_flag(args, "--roll") {
+        rules.roll = parse_glyph(&value);
+    }
+    if let Some(value) = take_flag(args, "--empty") {
+        rules.empty = parse_glyph(&value);
+    }
+    if rules.roll == rules.empty {
+        panic!("Roll and empty glyphs must differ");
+    }
+    rules
+}
+
 #[derive(Debug)]
-struct Map(HashMap<Point, char>);
+struct Map {
+    cells: HashMap<Point, char>,
+    rules: Rules,
+    bounds: (Point, Point),
+    // Sparse maps only store rolls: every other cell is empty.
+    sparse: bool,
+}
 
 impl Map {
-    fn remove_accessible_rolls(&mut self) -> usize {
-        let mut accessible_rolls: Vec<Point> = vec![];
-        for (point, value) in self.0.iter() {
-            if *value != '@' {
-                continue;
+    fn new(cells: HashMap<Point, char>, rules: Rules) -> Map {
+        let xs = cells.keys().map(|point| point.x);
+        let ys = cells.keys().map(|point| point.y);
+        let bounds = (
+            Point {
+                x: xs.clone().min().unwrap_or(0),
+                y: ys.clone().min().unwrap_or(0),
+            },
+            Point {
+                x: xs.max().unwrap_or(-1),
+                y: ys.max().unwrap_or(-1),
+            },
+        );
+        Map {
+            cells,
+            rules,
+            bounds,
+            sparse: false,
+        }
+    }
+
+    fn is_roll(&self, point: &Point) -> bool {
+        self.cells.get(point) == Some(&self.rules.roll)
+    }
+
+    fn rolls(&self) -> Vec<Point> {
+        self.cells
+            .iter()
+            .filter(|(_, value)| **value == self.rules.roll)
+            .map(|(point, _)| *point)
+            .collect()
+    }
+
+    fn neighbors(&self, point: &Point) -> Vec<Point> {
+        self.rules
+            .neighbourhood
+            .offsets()
+            .iter()
+            .filter_map(|(dx, dy)| self.offset(point, *dx as i128, *dy as i128))
+            .collect()
+    }
+
+    // The points that count `point` as one of their neighbors. It only differs
+    // from `neighbors` for custom neighbourhoods that aren't symmetric.
+    fn neighbor_of(&self, point: &Point) -> Vec<Point> {
+        self.rules
+            .neighbourhood
+            .offsets()
+            .iter()
+            .filter_map(|(dx, dy)| self.offset(point, -(*dx as i128), -(*dy as i128)))
+            .collect()
+    }
+
+    fn neighbor_rolls(&self, point: &Point) -> usize {
+        self.neighbors(point)
+            .iter()
+            .filter(|neighbor| self.is_roll(neighbor))
+            .count()
+    }
+
+    fn remove_rolls_by_round(&mut self) -> Vec<usize> {
+        self.remove_rolls()
+            .iter()
+            .map(|rolls| rolls.len())
+            .collect()
+    }
+
+    // Removes accessible rolls round after round until none is left, and
+    // returns the rolls removed in each round. Rather than rescanning the
+    // whole map every round, it keeps the neighbor count of every roll and only
+    // re-examines the neighbors of the rolls removed in the previous round.
+    fn remove_rolls(&mut self) -> Vec<Vec<Point>> {
+        let threshold = self.rules.threshold;
+        let mut neighbor_rolls: HashMap<Point, usize> = self
+            .rolls()
+            .into_iter()
+            .map(|point| (point, self.neighbor_rolls(&point)))
+            .collect();
+        let mut candidates: Vec<Point> = neighbor_rolls
+            .iter()
+            .filter(|(_, count)| **count < threshold)
+            .map(|(point, _)| *point)
+            .collect();
+
+        let mut removed_rolls_by_round = vec![];
+        while !candidates.is_empty() {
+            // Rolls are marked as removed right away, but neighbor counts are
+            // only updated once the round is over so removals stay simultaneous.
+            let mut removed_rolls = vec![];
+            for candidate in candidates {
+                if self.is_roll(&candidate) && neighbor_rolls[&candidate] < threshold {
+                    self.cells.insert(candidate, self.rules.empty);
+                    removed_rolls.push(candidate);
+                }
+            }
+            if removed_rolls.is_empty() {
+                break;
+            }
+            candidates = vec![];
+            for removed_roll in &removed_rolls {
+                for neighbor in self.neighbor_of(removed_roll) {
+                    if !self.is_roll(&neighbor) {
+                        continue;
+                    }
+                    let count = neighbor_rolls.get_mut(&neighbor).unwrap();
+                    *count -= 1;
+                    if *count + 1 == threshold {
+                        candidates.push(neighbor);
+                    }
+                }
             }
+            removed_rolls_by_round.push(removed_rolls);
+        }
 
-            let neighbor_rolls = point
-                .neighors()
-                .iter()
-                .map(|neighbor| match self.0.get(neighbor) {
-                    Some(value) if *value == '@' => 1,
-                    _ => 0,
-                })
-                .sum::<u64>();
-            if neighbor_rolls < 4 {
-                accessible_rolls.push(Point {
-                    x: point.x,
-                    y: point.y,
-                });
+        removed_rolls_by_round
+    }
+
+    // The core number of a roll is the highest threshold it survives: peeling
+    // with threshold `t` keeps exactly the rolls whose core number is at least
+    // `t`. Rolls are peeled in increasing order of their remaining neighbor
+    // count with one bucket per count, so every threshold is covered at once.
+    fn core_numbers(&self) -> HashMap<Point, usize> {
+        let mut neighbor_rolls: HashMap<Point, usize> = self
+            .rolls()
+            .into_iter()
+            .map(|point| (point, self.neighbor_rolls(&point)))
+            .collect();
+        let mut buckets = vec![vec![]; self.rules.neighbourhood.offsets().len() + 1];
+        for (point, count) in &neighbor_rolls {
+            buckets[*count].push(*point);
+        }
+
+        let mut core_numbers = HashMap::new();
+        for core_number in 0..buckets.len() {
+            // A roll is pushed again each time its count drops, so stale
+            // entries in higher buckets are skipped once it has been peeled.
+            while let Some(roll) = buckets[core_number].pop() {
+                if core_numbers.contains_key(&roll) {
+                    continue;
+                }
+                core_numbers.insert(roll, core_number);
+                for neighbor in self.neighbor_of(&roll) {
+                    if core_numbers.contains_key(&neighbor) {
+                        continue;
+                    }
+                    if let Some(count) = neighbor_rolls.get_mut(&neighbor)
+                        && *count > core_number
+                    {
+                        *count -= 1;
+                        buckets[*count].push(neighbor);
+                    }
+                }
+            }
+        }
+
+        core_numbers
+    }
+
+    fn bounds(&self) -> (Point, Point) {
+        self.bounds
+    }
+
+    // The point `(dx, dy)` away from `point`, wrapped around the map for the
+    // toroidal topology. Coordinates are computed as `i128` so that sparse maps
+    // can use the whole `i64` range, and points outside of it are `None`.
+    fn offset(&self, point: &Point, dx: i128, dy: i128) -> Option<Point> {
+        let (mut x, mut y) = (point.x as i128 + dx, point.y as i128 + dy);
+        if self.rules.topology == Topology::Toroidal {
+            let (min, max) = self.bounds;
+            let (min_x, min_y) = (min.x as i128, min.y as i128);
+            x = min_x + (x - min_x).rem_euclid(max.x as i128 - min_x + 1);
+            y = min_y + (y - min_y).rem_euclid(max.y as i128 - min_y + 1);
+        }
+        Some(Point {
+            x: x.try_into().ok()?,
+            y: y.try_into().ok()?,
+        })
+    }
+
+    // Applies `rule` to every cell of the map at once, and returns how many
+    // cells changed. Sparse maps also evaluate the empty cells next to stored
+    // ones, and drop the cells that become empty.
+    fn step(&mut self, rule: &dyn CellRule) -> usize {
+        let mut points: HashSet<Point> = self.cells.keys().copied().collect();
+        if self.sparse {
+            for point in self.cells.keys() {
+                points.extend(self.neighbor_of(point));
+            }
+        }
+
+        let changes: Vec<(Point, char)> = points
+            .into_iter()
+            .filter_map(|point| {
+                let cell = *self.cells.get(&point).unwrap_or(&self.rules.empty);
+                let mut neighbor_counts = HashMap::new();
+                for neighbor in self.neighbors(&point) {
+                    let state = self.cells.get(&neighbor).unwrap_or(&self.rules.empty);
+                    *neighbor_counts.entry(*state).or_insert(0) += 1;
+                }
+                let next = rule.next(cell, &neighbor_counts);
+                (next != cell).then_some((point, next))
+            })
+            .collect();
+
+        let result = changes.len();
+        for (point, next) in changes {
+            if self.sparse && next == self.rules.empty {
+                self.cells.remove(&point);
+            } else {
+                self.cells.insert(point, next);
+            }
+        }
+        result
+    }
+
+    // Steps until the map stops changing, comes back to an earlier state, or
+    // `max_steps` steps have been made.
+    fn run(&mut self, rule: &dyn CellRule, max_steps: usize) -> Outcome {
+        let mut seen = HashMap::from([(self.state_hash(), 0)]);
+        for step in 1..=max_steps {
+            if self.step(rule) == 0 {
+                return Outcome::FixedPoint { steps: step - 1 };
+            }
+            if let Some(start) = seen.insert(self.state_hash(), step) {
+                return Outcome::Cycle {
+                    start,
+                    period: step - start,
+                };
             }
         }
 
+        Outcome::StepLimit
+    }
+
+    fn state_hash(&self) -> u64 {
+        let mut cells: Vec<(&Point, &char)> = self.cells.iter().collect();
+        cells.sort_by_key(|(point, _)| (point.y, point.x));
+        let mut hasher = DefaultHasher::new();
+        cells.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn render(&self) -> String {
+        if self.sparse {
+            render_sparse_removal_rounds(self, &HashMap::new())
+        } else {
+            render_removal_rounds(self, &HashMap::new(), false)
+        }
+    }
+
+    #[cfg(test)]
+    fn remove_accessible_rolls(&mut self) -> usize {
+        let accessible_rolls: Vec<Point> = self
+            .rolls()
+            .into_iter()
+            .filter(|point| self.neighbor_rolls(point) < self.rules.threshold)
+            .collect();
+
         let result = accessible_rolls.len();
         for accessible_roll in accessible_rolls {
-            self.0.insert(accessible_roll, '.');
+            self.cells.insert(accessible_roll, self.rules.empty);
+        }
+
+        result
+    }
+}
+
+// A cellular-automaton rule: the next state of a cell given its current state
+// and how many of its neighbours are in each state.
+trait CellRule {
+    fn next(&self, cell: char, neighbor_counts: &HashMap<char, usize>) -> char;
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    // The map stopped changing after `steps` steps.
+    FixedPoint { steps: usize },
+    // The map after `start + period` steps is the same as after `start` steps.
+    Cycle { start: usize, period: usize },
+    StepLimit,
+}
+
+// Day4's rule: a roll with fewer than `threshold` neighbouring rolls is removed.
+struct RollRemoval {
+    roll: char,
+    empty: char,
+    threshold: usize,
+}
+
+impl From<&Rules> for RollRemoval {
+    fn from(rules: &Rules) -> Self {
+        RollRemoval {
+            roll: rules.roll,
+            empty: rules.empty,
+            threshold: rules.threshold,
+        }
+    }
+}
+
+impl CellRule for RollRemoval {
+    fn next(&self, cell: char, neighbor_counts: &HashMap<char, usize>) -> char {
+        let rolls = neighbor_counts.get(&self.roll).copied().unwrap_or(0);
+        if cell == self.roll && rolls < self.threshold {
+            self.empty
+        } else {
+            cell
+        }
+    }
+}
+
+// Game-of-Life-like rules written as `B3/S23`: a dead cell comes alive with
+// one of the birth counts of alive neighbours, and an alive cell stays alive
+// with one of the survival counts.
+struct LifeLike {
+    alive: char,
+    dead: char,
+    birth: Vec<usize>,
+    survival: Vec<usize>,
+}
+
+impl LifeLike {
+    fn parse(rule: &str, rules: &Rules) -> LifeLike {
+        let counts = |part: &str, prefix: char| -> Vec<usize> {
+            part.strip_prefix(prefix)
+                .unwrap_or_else(|| panic!("Invalid rule `{rule}`"))
+                .chars()
+                .map(|count| count.to_digit(10).expect("Invalid neighbour count") as usize)
+                .collect()
+        };
+        let (birth, survival) = rule
+            .split_once('/')
+            .unwrap_or_else(|| panic!("Invalid rule `{rule}`"));
+        LifeLike {
+            alive: rules.roll,
+            dead: rules.empty,
+            birth: counts(birth, 'B'),
+            survival: counts(survival, 'S'),
+        }
+    }
+}
+
+impl CellRule for LifeLike {
+    fn next(&self, cell: char, neighbor_counts: &HashMap<char, usize>) -> char {
+        let alive = neighbor_counts.get(&self.alive).copied().unwrap_or(0);
+        let counts = if cell == self.alive {
+            &self.survival
+        } else {
+            &self.birth
+        };
+        if counts.contains(&alive) {
+            self.alive
+        } else {
+            self.dead
+        }
+    }
+}
+
+// The round (starting at 1) in which each removed roll became accessible.
+fn removal_rounds(removed_rolls_by_round: &[Vec<Point>]) -> HashMap<Point, usize> {
+    removed_rolls_by_round
+        .iter()
+        .enumerate()
+        .flat_map(|(round, rolls)| rolls.iter().map(move |roll| (*roll, round + 1)))
+        .collect()
+}
+
+fn round_glyph(round: usize) -> char {
+    match round {
+        1..=35 => char::from_digit(round as u32, 36).unwrap(),
+        _ => '+',
+    }
+}
+
+// Draws the map with removed rolls replaced by the glyph of their round and
+// surviving rolls kept as the roll glyph of the rules. With `colour`, rounds
+// also get an ANSI colour going from red (first rounds) to blue (last rounds).
+fn render_removal_rounds(
+    map: &Map,
+    removal_rounds: &HashMap<Point, usize>,
+    colour: bool,
+) -> String {
+    const PALETTE: [u8; 12] = [196, 202, 208, 214, 220, 226, 190, 118, 48, 51, 39, 21];
+    let last_round = removal_rounds.values().copied().max().unwrap_or(1);
+    let (min, max) = map.bounds();
+    let mut result = String::new();
+
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let point = Point { x, y };
+            match removal_rounds.get(&point) {
+                Some(round) if colour => {
+                    let shade =
+                        PALETTE[(round - 1) * (PALETTE.len() - 1) / (last_round - 1).max(1)];
+                    result += &format!("\x1b[38;5;{shade}m{}\x1b[0m", round_glyph(*round));
+                }
+                Some(round) => result.push(round_glyph(*round)),
+                None => result.push(*map.cells.get(&point).unwrap_or(&' ')),
+            }
+        }
+        result.push('\n');
+    }
+
+    result
+}
+
+// The number of rolls left once peeling stops, for every threshold from 0 up
+// to the first one that removes every roll.
+fn survivors_by_threshold(core_numbers: &HashMap<Point, usize>) -> Vec<usize> {
+    let max_core_number = core_numbers.values().copied().max();
+    let mut survivors = vec![0; max_core_number.map_or(1, |max| max + 2)];
+    for core_number in core_numbers.values() {
+        survivors[*core_number] += 1;
+    }
+    for threshold in (0..survivors.len() - 1).rev() {
+        survivors[threshold] += survivors[threshold + 1];
+    }
+
+    survivors
+}
+
+fn render_survivors(survivors: &[usize]) -> String {
+    let mut result = "threshold survivors\n".to_owned();
+    for (threshold, count) in survivors.iter().enumerate() {
+        result += &format!("{threshold:>9} {count:>9}\n");
+    }
+
+    result
+}
+
+// Lists every roll of the map as `x,y glyph`, with the same glyphs as
+// `render_removal_rounds`, for maps too sparse to be drawn.
+fn render_sparse_removal_rounds(map: &Map, removal_rounds: &HashMap<Point, usize>) -> String {
+    let mut rolls: Vec<Point> = removal_rounds.keys().copied().chain(map.rolls()).collect();
+    rolls.sort_by_key(|point| (point.y, point.x));
+
+    let mut result = String::new();
+    for roll in rolls {
+        let glyph = removal_rounds
+            .get(&roll)
+            .map_or(map.rules.roll, |round| round_glyph(*round));
+        result += &format!("{},{} {glyph}\n", roll.x, roll.y);
+    }
+
+    result
+}
+
+// The rolls still standing once `round` rounds of removal are over.
+fn survivors_after(
+    initial_rolls: &[Point],
+    removal_rounds: &HashMap<Point, usize>,
+    round: usize,
+) -> HashSet<Point> {
+    initial_rolls
+        .iter()
+        .filter(|roll| {
+            removal_rounds
+                .get(roll)
+                .is_none_or(|removed| *removed > round)
+        })
+        .copied()
+        .collect()
+}
+
+#[derive(Debug)]
+struct Component {
+    rolls: Vec<Point>,
+    min: Point,
+    max: Point,
+}
+
+impl Component {
+    fn to_grid(&self, rules: &Rules) -> String {
+        let rolls: HashSet<&Point> = self.rolls.iter().collect();
+        let mut result = String::new();
+        for y in self.min.y..=self.max.y {
+            for x in self.min.x..=self.max.x {
+                result.push(if rolls.contains(&Point { x, y }) {
+                    rules.roll
+                } else {
+                    rules.empty
+                });
+            }
+            result.push('\n');
         }
 
         result
     }
 }
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+// Groups rolls touching each other, diagonals included, from the largest group
+// to the smallest. Edges never wrap, even for toroidal maps.
+fn components(rolls: &HashSet<Point>) -> Vec<Component> {
+    let mut visited: HashSet<Point> = HashSet::new();
+    let mut components = vec![];
+
+    for start in rolls {
+        if !visited.insert(*start) {
+            continue;
+        }
+        let mut component = Component {
+            rolls: vec![],
+            min: *start,
+            max: *start,
+        };
+        let mut stack = vec![*start];
+        while let Some(roll) = stack.pop() {
+            component.rolls.push(roll);
+            component.min.x = component.min.x.min(roll.x);
+            component.min.y = component.min.y.min(roll.y);
+            component.max.x = component.max.x.max(roll.x);
+            component.max.y = component.max.y.max(roll.y);
+            for (dx, dy) in Neighbourhood::Moore.offsets() {
+                let Some(neighbor) = roll.offset(dx, dy) else {
+                    continue;
+                };
+                if rolls.contains(&neighbor) && visited.insert(neighbor) {
+                    stack.push(neighbor);
+                }
+            }
+        }
+        component.rolls.sort_by_key(|point| (point.y, point.x));
+        components.push(component);
+    }
+
+    components.sort_by_key(|component| {
+        (
+            Reverse(component.rolls.len()),
+            component.min.y,
+            component.min.x,
+        )
+    });
+    components
+}
+
+fn render_components(components: &[Component], rules: &Rules, grids: bool) -> String {
+    let mut result = String::new();
+    for component in components {
+        result += &format!(
+            "{:>6} rolls from {},{} to {},{}\n",
+            component.rolls.len(),
+            component.min.x,
+            component.min.y,
+            component.max.x,
+            component.max.y
+        );
+        if grids {
+            result += &component.to_grid(rules);
+        }
+    }
+
+    result
+}
+
+fn render_round_counts(initial_rolls: usize, removal_rounds: &HashMap<Point, usize>) -> String {
+    let mut counts = vec![0; removal_rounds.values().copied().max().unwrap_or(0)];
+    for round in removal_rounds.values() {
+        counts[round - 1] += 1;
+    }
+
+    let mut remaining_rolls = initial_rolls;
+    let mut result = "round glyph removed remaining\n".to_owned();
+    for (index, count) in counts.iter().enumerate() {
+        remaining_rolls -= count;
+        result += &format!(
+            "{:>5} {:>5} {count:>7} {remaining_rolls:>9}\n",
+            index + 1,
+            round_glyph(index + 1)
+        );
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
 struct Point {
     x: i64,
     y: i64,
 }
 
 impl Point {
-    fn neighors(&self) -> [Point; 8] {
-        [
-            Point {
-                x: self.x - 1,
-                y: self.y - 1,
-            },
-            Point {
-                x: self.x,
-                y: self.y - 1,
-            },
-            Point {
-                x: self.x + 1,
-                y: self.y - 1,
-            },
-            Point {
-                x: self.x - 1,
-                y: self.y,
-            },
-            Point {
-                x: self.x + 1,
-                y: self.y,
-            },
-            Point {
-                x: self.x - 1,
-                y: self.y + 1,
-            },
-            Point {
-                x: self.x,
-                y: self.y + 1,
-            },
-            Point {
-                x: self.x + 1,
-                y: self.y + 1,
-            },
-        ]
+    fn offset(&self, dx: i64, dy: i64) -> Option<Point> {
+        Some(Point {
+            x: self.x.checked_add(dx)?,
+            y: self.y.checked_add(dy)?,
+        })
     }
 }
 
-fn parse_input(path: &str) -> Map {
+fn parse_input(path: &str, rules: Rules, sparse: bool) -> Map {
     let content = fs::read_to_string(path).unwrap();
-    let mut map = HashMap::new();
+    if sparse {
+        parse_sparse(&content, rules)
+    } else {
+        parse(&content, rules)
+    }
+}
+
+fn parse(content: &str, rules: Rules) -> Map {
+    let mut cells = HashMap::new();
 
     for (y, line) in content.lines().enumerate() {
         for (x, char) in line.chars().enumerate() {
-            map.insert(
+            cells.insert(
                 Point {
                     x: x as i64,
                     y: y as i64,
@@ -114,5 +829,376 @@ fn parse_input(path: &str) -> Map {
         }
     }
 
-    Map(map)
+    Map::new(cells, rules)
+}
+
+// Parses one `x,y` roll per line. Every other cell is empty, so rolls can be
+// arbitrarily far apart.
+fn parse_sparse(content: &str, rules: Rules) -> Map {
+    let cells = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (x, y) = line
+                .split_once(',')
+                .unwrap_or_else(|| panic!("Invalid roll `{line}`"));
+            let point = Point {
+                x: x.trim().parse().expect("Invalid x coordinate"),
+                y: y.trim().parse().expect("Invalid y coordinate"),
+            };
+            (point, rules.roll)
+        })
+        .collect();
+
+    Map {
+        sparse: true,
+        ..Map::new(cells, rules)
+    }
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "..@@.@@@@.
+@@@.@@@.@.
+@@@@@.@.@@
+@.@@@@..@.
+@@.@@@@.@@
+.@@@@@@@.@
+.@.@.@.@@@
+@.@@@.@@@@
+.@@@@@@@@.
+@.@.@@@.@.";
+
+#[test]
+fn test_remove_rolls_by_round_example() {
+    let removed_rolls_by_round = parse(EXAMPLE, Rules::default()).remove_rolls_by_round();
+
+    assert_eq!(removed_rolls_by_round[0], 13);
+    assert_eq!(removed_rolls_by_round.iter().sum::<usize>(), 43);
+}
+
+#[cfg(test)]
+fn random_grid(size: usize, seed: u64) -> String {
+    let mut state = seed;
+    (0..size)
+        .map(|_| {
+            (0..size)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    if (state >> 33) % 10 < 7 { '@' } else { '.' }
+                })
+                .collect::<String>()
+                + "\n"
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn assert_matches_rescan(content: &str, rules: Rules) {
+    let mut rescanned_map = parse(content, rules.clone());
+    let mut rescanned = vec![];
+    loop {
+        let removed_rolls = rescanned_map.remove_accessible_rolls();
+        if removed_rolls == 0 {
+            break;
+        }
+        rescanned.push(removed_rolls);
+    }
+
+    let mut map = parse(content, rules);
+    assert_eq!(map.remove_rolls_by_round(), rescanned);
+    assert_eq!(map.cells, rescanned_map.cells);
+}
+
+#[test]
+fn test_remove_rolls_by_round_matches_rescan() {
+    assert_matches_rescan(&random_grid(40, 7), Rules::default());
+}
+
+#[test]
+fn test_rules_match_rescan() {
+    let content = random_grid(30, 11);
+    for (neighbourhood, threshold) in [
+        ("4", 2),
+        ("8", 6),
+        ("hex", 3),
+        ("1,0;0,1;1,1;2,0", 3),
+        ("0,-2;-1,0;1,0;0,2;2,2", 3),
+    ] {
+        let rules = Rules {
+            neighbourhood: parse_neighbourhood(neighbourhood),
+            threshold,
+            ..Rules::default()
+        };
+        assert_matches_rescan(&content, rules);
+    }
+
+    let glyphs = Rules {
+        roll: '#',
+        empty: ' ',
+        ..Rules::default()
+    };
+    let mut map = parse(&EXAMPLE.replace('@', "#").replace('.', " "), glyphs);
+    assert_eq!(map.remove_rolls_by_round()[0], 13);
+    assert!(
+        map.cells
+            .values()
+            .all(|value| *value == '#' || *value == ' ')
+    );
+}
+
+#[test]
+fn test_parse_rules() {
+    let mut args: Vec<String> = [
+        "rounds",
+        "--neighbourhood",
+        "0,1;-1,0",
+        "--threshold",
+        "2",
+        "--roll",
+        "#",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+    let rules = parse_rules(&mut args);
+
+    assert_eq!(args, ["rounds"]);
+    assert_eq!(
+        rules.neighbourhood,
+        Neighbourhood::Custom(vec![(0, 1), (-1, 0)])
+    );
+    assert_eq!(rules.threshold, 2);
+    assert_eq!((rules.roll, rules.empty), ('#', '.'));
+}
+
+#[test]
+fn test_removal_rounds() {
+    let mut map = parse("@@@\n@@@\n@@@", Rules::default());
+    let removal_rounds = removal_rounds(&map.remove_rolls());
+
+    assert_eq!(removal_rounds[&Point { x: 0, y: 0 }], 1);
+    assert_eq!(removal_rounds[&Point { x: 1, y: 0 }], 2);
+    assert_eq!(removal_rounds[&Point { x: 1, y: 1 }], 3);
+    assert_eq!(
+        render_removal_rounds(&map, &removal_rounds, false),
+        "121\n232\n121\n"
+    );
+    assert_eq!(
+        render_round_counts(9, &removal_rounds),
+        "round glyph removed remaining\n    1     1       4         5\n    2     2       4         1\n    3     3       1         0\n"
+    );
+}
+
+#[test]
+fn test_core_numbers_match_peeling() {
+    let content = random_grid(30, 3);
+    for neighbourhood in ["8", "4", "hex", "1,0;0,1;1,1;2,0"] {
+        let rules = Rules {
+            neighbourhood: parse_neighbourhood(neighbourhood),
+            ..Rules::default()
+        };
+        let map = parse(&content, rules.clone());
+        let survivors = survivors_by_threshold(&map.core_numbers());
+        assert_eq!(*survivors.last().unwrap(), 0);
+
+        for (threshold, count) in survivors.iter().enumerate() {
+            let mut map = parse(
+                &content,
+                Rules {
+                    threshold,
+                    ..rules.clone()
+                },
+            );
+            map.remove_rolls();
+            assert_eq!(map.rolls().len(), *count, "{neighbourhood} {threshold}");
+        }
+    }
+}
+
+#[test]
+fn test_survivors_by_threshold() {
+    let map = parse("@@@\n@@@\n@@@", Rules::default());
+    let core_numbers = map.core_numbers();
+
+    assert_eq!(core_numbers[&Point { x: 0, y: 0 }], 3);
+    assert_eq!(core_numbers[&Point { x: 1, y: 1 }], 3);
+    assert_eq!(survivors_by_threshold(&core_numbers), [9, 9, 9, 9, 0]);
+    assert_eq!(survivors_by_threshold(&HashMap::new()), [0]);
+}
+
+#[test]
+fn test_toroidal_topology() {
+    let rules = Rules {
+        topology: Topology::Toroidal,
+        ..Rules::default()
+    };
+
+    // Every roll of a full grid has 8 neighbours once edges wrap around.
+    let mut map = parse("@@@@\n@@@@\n@@@@\n@@@@", rules.clone());
+    assert!(map.remove_rolls().is_empty());
+
+    let map = parse("@..\n...\n..@", rules.clone());
+    assert_eq!(map.neighbor_rolls(&Point { x: 0, y: 0 }), 1);
+
+    let mut map = parse(EXAMPLE, rules.clone());
+    let survivors = map.rolls().len() - map.remove_rolls().iter().flatten().count();
+    assert_eq!(
+        survivors_by_threshold(&parse(EXAMPLE, rules.clone()).core_numbers())[4],
+        survivors
+    );
+    assert_matches_rescan(&random_grid(20, 5), rules);
+}
+
+#[test]
+fn test_sparse_map() {
+    let content = "1000000000000,5\n1000000000001,5\n1000000000000,6\n1000000000001,6\n1000000000002,6\n-7,-7\n";
+    let mut map = parse_sparse(content, Rules::default());
+
+    assert_eq!(map.rolls().len(), 6);
+    let removal_rounds = removal_rounds(&map.remove_rolls());
+    assert_eq!(
+        render_sparse_removal_rounds(&map, &removal_rounds),
+        "-7,-7 1\n1000000000000,5 1\n1000000000001,5 2\n1000000000000,6 1\n1000000000001,6 2\n1000000000002,6 1\n"
+    );
+}
+
+#[test]
+fn test_components() {
+    let map = parse("@@..@\n@...@\n.....\n..@..\n...@.", Rules::default());
+    let components = components(&map.rolls().into_iter().collect());
+
+    assert_eq!(components.len(), 3);
+    assert_eq!(
+        render_components(&components, &map.rules, true),
+        "     3 rolls from 0,0 to 1,1\n@@\n@.\n     2 rolls from 4,0 to 4,1\n@\n@\n     2 rolls from 2,3 to 3,4\n@.\n.@\n"
+    );
+}
+
+#[test]
+fn test_components_of_survivors() {
+    let mut map = parse(EXAMPLE, Rules::default());
+    let initial_rolls = map.rolls();
+    let removal_rounds = removal_rounds(&map.remove_rolls());
+
+    let after_first_round = survivors_after(&initial_rolls, &removal_rounds, 1);
+    assert_eq!(after_first_round.len(), initial_rolls.len() - 13);
+    let at_fixed_point = survivors_after(&initial_rolls, &removal_rounds, usize::MAX);
+    assert_eq!(at_fixed_point, map.rolls().into_iter().collect());
+
+    let components = components(&at_fixed_point);
+    assert_eq!(components.len(), 1);
+    assert_eq!(components[0].rolls.len(), 28);
+    assert_eq!(
+        (components[0].min, components[0].max),
+        (Point { x: 3, y: 3 }, Point { x: 8, y: 9 })
+    );
+    assert!(
+        components[0]
+            .to_grid(&map.rules)
+            .starts_with(".@@...\n@@@@..\n")
+    );
+}
+
+#[test]
+fn test_roll_removal_automaton() {
+    let mut map = parse(EXAMPLE, Rules::default());
+    let rounds = parse(EXAMPLE, Rules::default()).remove_rolls();
+    let mut expected = parse(EXAMPLE, Rules::default());
+    expected.remove_rolls();
+
+    let rule = RollRemoval::from(&map.rules);
+    assert_eq!(map.step(&rule), 13);
+    assert_eq!(
+        map.run(&rule, 100),
+        Outcome::FixedPoint {
+            steps: rounds.len() - 1
+        }
+    );
+    assert_eq!(map.cells, expected.cells);
+}
+
+#[test]
+fn test_life_like_automaton() {
+    let rules = Rules {
+        topology: Topology::Toroidal,
+        ..Rules::default()
+    };
+    let life = LifeLike::parse("B3/S23", &rules);
+
+    let mut block = parse("....\n.@@.\n.@@.\n....", rules.clone());
+    assert_eq!(block.run(&life, 10), Outcome::FixedPoint { steps: 0 });
+
+    let mut blinker = parse(".....\n.....\n.@@@.\n.....\n.....", rules.clone());
+    assert_eq!(
+        blinker.run(&life, 10),
+        Outcome::Cycle {
+            start: 0,
+            period: 2
+        }
+    );
+    assert_eq!(blinker.render(), ".....\n.....\n.@@@.\n.....\n.....\n");
+
+    // A glider moves one cell diagonally every 4 steps, so it comes back to
+    // its starting place on a 6x6 torus after 24 steps.
+    let glider = ".@....\n..@...\n@@@...\n......\n......\n......";
+    assert_eq!(
+        parse(glider, rules.clone()).run(&life, 100),
+        Outcome::Cycle {
+            start: 0,
+            period: 24
+        }
+    );
+    assert_eq!(parse(glider, rules).run(&life, 10), Outcome::StepLimit);
+}
+
+#[test]
+fn test_sparse_map_at_i64_limits() {
+    let content = format!("{},0\n{},0\n0,2\n", i64::MIN, i64::MAX);
+    let edge = Point { x: i64::MAX, y: 0 };
+
+    let mut map = parse_sparse(&content, Rules::default());
+    assert_eq!(map.neighbor_rolls(&edge), 0);
+    assert_eq!(map.remove_rolls_by_round(), [3]);
+
+    let toroidal = Rules {
+        topology: Topology::Toroidal,
+        ..Rules::default()
+    };
+    let map = parse_sparse(&content, toroidal);
+    assert_eq!(map.neighbor_rolls(&edge), 1);
+    assert_eq!(map.core_numbers()[&edge], 1);
+    assert_eq!(components(&map.rolls().into_iter().collect()).len(), 3);
+}
+
+#[test]
+fn test_sparse_automaton() {
+    let life = LifeLike::parse("B3/S23", &Rules::default());
+
+    let mut blinker = parse_sparse("1,2\n2,2\n3,2", Rules::default());
+    assert_eq!(
+        blinker.run(&life, 10),
+        Outcome::Cycle {
+            start: 0,
+            period: 2
+        }
+    );
+    assert_eq!(blinker.step(&life), 4);
+    assert_eq!(blinker.render(), "2,1 @\n2,2 @\n2,3 @\n");
+    assert_eq!(blinker.cells.len(), 3);
+
+    // Far apart rolls are rendered as a list rather than a grid.
+    let mut map = parse_sparse(
+        "0,0\n1,0\n0,1\n1,1\n1000000000000,1000000000000",
+        Rules {
+            threshold: 3,
+            ..Rules::default()
+        },
+    );
+    assert_eq!(
+        map.run(&RollRemoval::from(&map.rules), 10),
+        Outcome::FixedPoint { steps: 1 }
+    );
+    assert_eq!(map.render(), "0,0 @\n1,0 @\n0,1 @\n1,1 @\n");
 }
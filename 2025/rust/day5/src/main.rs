@@ -1,7 +1,62 @@
-use std::{fs, ops::RangeInclusive};
+use std::{collections::BTreeMap, env, fs, ops::RangeInclusive};
 
 fn main() {
-    let (db, ingredients) = parse_input("src/input");
+    let (ranges, ingredients) = parse_input("src/input");
+    let mut db = FreshDb::new(ranges.clone());
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(|mode| mode.as_str()) {
+        Some("compare") => {
+            let (other, _) = parse_input(args.get(1).expect("Missing database to compare with"));
+            let other = FreshDb::new(other);
+            dbg!(db.union(&other).count());
+            dbg!(db.intersection(&other).count());
+            dbg!(db.difference(&other).count());
+            dbg!(other.difference(&db).count());
+            dbg!(db.symmetric_difference(&other).count());
+            dbg!(db.is_subset(&other));
+            dbg!(other.is_subset(&db));
+            dbg!(db.overlaps(&other));
+            return;
+        }
+        // Applies one `+start-end` or `-start-end` change per line of a file.
+        Some("apply") => {
+            let changes =
+                fs::read_to_string(args.get(1).expect("Missing changes")).expect("Missing changes");
+            for line in changes.lines() {
+                match line.split_at_checked(1) {
+                    Some(("+", range)) => db.insert_range(parse_range(range)),
+                    Some(("-", range)) => db.remove_range(parse_range(range)),
+                    _ => panic!("Invalid change `{line}`"),
+                }
+            }
+        }
+        Some("depth") => {
+            let coverage = Coverage::new(&ranges);
+            let (max_depth, ranges) = coverage.max_depth();
+            dbg!(max_depth);
+            for range in ranges {
+                println!("{}-{}", range.start(), range.end());
+            }
+            for depth in 1..=max_depth {
+                println!("{depth:>3} {}", coverage.covered_at_least(depth));
+            }
+            for ingredient in &args[1..] {
+                let ingredient = ingredient.parse().expect("Invalid ingredient");
+                println!("{ingredient} {}", coverage.depth_at(ingredient));
+            }
+            return;
+        }
+        Some("gaps") => {
+            let bound = parse_range(args.get(1).expect("Missing bound"));
+            for range in db.complement(bound).ranges() {
+                println!("{}-{}", range.start(), range.end());
+            }
+            return;
+        }
+        _ => {}
+    }
+
     let fresh_ingredients = ingredients
         .iter()
         .filter(|ingredient| db.contains(ingredient))
@@ -16,54 +71,509 @@ fn main() {
 type IngredientId = u64;
 type Ingredients = Vec<IngredientId>;
 
-struct FreshDb(Vec<RangeInclusive<IngredientId>>);
+// Ranges are stored by start, without overlapping or touching each other, and
+// the number of ids they cover is kept up to date as they change.
+#[derive(Debug, Default)]
+struct FreshDb {
+    ranges: BTreeMap<IngredientId, IngredientId>,
+    count: u128,
+}
 
 impl FreshDb {
     pub fn new(ranges: Vec<RangeInclusive<IngredientId>>) -> FreshDb {
-        let mut _ranges = ranges;
-        _ranges.sort_by_key(|range| *range.start());
-        FreshDb(_ranges)
+        let mut db = FreshDb::default();
+        for range in ranges {
+            db.insert_range(range);
+        }
+        db
+    }
+
+    pub fn ranges(&self) -> Vec<RangeInclusive<IngredientId>> {
+        self.ranges
+            .iter()
+            .map(|(start, end)| *start..=*end)
+            .collect()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
     pub fn contains(&self, ingredient_id: &IngredientId) -> bool {
-        self.0.iter().any(|range| range.contains(ingredient_id))
-    }
-
-    pub fn count(&self) -> u64 {
-        let mut merged_ranges = vec![self.0[0].clone()];
-        let mut last = self.0[0].clone();
-        for range in &self.0 {
-            if range.start() > last.end() {
-                last = *range.start()..=*range.end();
-                merged_ranges.push(last.clone());
-            } else if range.end() >= last.end() {
-                merged_ranges.pop().unwrap();
-                last = *last.start()..=*range.end();
-                merged_ranges.push(last.clone());
+        self.ranges
+            .range(..=ingredient_id)
+            .next_back()
+            .is_some_and(|(_, end)| end >= ingredient_id)
+    }
+
+    pub fn count(&self) -> u128 {
+        self.count
+    }
+
+    fn put(&mut self, start: IngredientId, end: IngredientId) {
+        self.ranges.insert(start, end);
+        self.count += (end - start) as u128 + 1;
+    }
+
+    fn take(&mut self, start: IngredientId) -> IngredientId {
+        let end = self.ranges.remove(&start).unwrap();
+        self.count -= (end - start) as u128 + 1;
+        end
+    }
+
+    pub fn insert_range(&mut self, range: RangeInclusive<IngredientId>) {
+        if range.is_empty() {
+            return;
+        }
+        let (mut start, mut end) = (*range.start(), *range.end());
+        if let Some((previous_start, previous_end)) = self.ranges.range(..start).next_back()
+            && previous_end.saturating_add(1) >= start
+        {
+            start = *previous_start;
+        }
+
+        // Every range starting inside the new one, or right after it, is merged.
+        let merged: Vec<IngredientId> = self
+            .ranges
+            .range(start..=end.saturating_add(1))
+            .map(|(start, _)| *start)
+            .collect();
+        for merged_start in merged {
+            end = end.max(self.take(merged_start));
+        }
+        self.put(start, end);
+    }
+
+    pub fn remove_range(&mut self, range: RangeInclusive<IngredientId>) {
+        if range.is_empty() {
+            return;
+        }
+        let (start, end) = (*range.start(), *range.end());
+        let mut overlapping: Vec<IngredientId> = self
+            .ranges
+            .range(..start)
+            .next_back()
+            .filter(|(_, previous_end)| **previous_end >= start)
+            .map(|(previous_start, _)| *previous_start)
+            .into_iter()
+            .collect();
+        overlapping.extend(self.ranges.range(start..=end).map(|(start, _)| *start));
+
+        for overlapping_start in overlapping {
+            let overlapping_end = self.take(overlapping_start);
+            if overlapping_start < start {
+                self.put(overlapping_start, start - 1);
+            }
+            if overlapping_end > end {
+                self.put(end + 1, overlapping_end);
             }
         }
+    }
 
-        merged_ranges
-            .iter()
-            .map(|range| range.end() - range.start() + 1)
+    pub fn union(&self, other: &FreshDb) -> FreshDb {
+        FreshDb::new(self.ranges().into_iter().chain(other.ranges()).collect())
+    }
+
+    pub fn intersection(&self, other: &FreshDb) -> FreshDb {
+        let (a_ranges, b_ranges) = (self.ranges(), other.ranges());
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+        while let (Some(a), Some(b)) = (a_ranges.get(i), b_ranges.get(j)) {
+            let start = *a.start().max(b.start());
+            let end = *a.end().min(b.end());
+            if start <= end {
+                ranges.push(start..=end);
+            }
+            if a.end() < b.end() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        FreshDb::new(ranges)
+    }
+
+    pub fn difference(&self, other: &FreshDb) -> FreshDb {
+        self.intersection(&other.complement(0..=IngredientId::MAX))
+    }
+
+    pub fn symmetric_difference(&self, other: &FreshDb) -> FreshDb {
+        self.difference(other).union(&other.difference(self))
+    }
+
+    // The ids of `bound` that aren't in the database.
+    pub fn complement(&self, bound: RangeInclusive<IngredientId>) -> FreshDb {
+        let mut ranges = vec![];
+        // The first id of `bound` not yet known to be covered, if any is left.
+        let mut next = Some(*bound.start());
+        for (range_start, range_end) in &self.ranges {
+            let Some(start) = next else {
+                break;
+            };
+            if range_start > bound.end() {
+                break;
+            }
+            if *range_end < start {
+                continue;
+            }
+            if *range_start > start {
+                ranges.push(start..=range_start - 1);
+            }
+            next = range_end.checked_add(1);
+        }
+        if let Some(start) = next
+            && start <= *bound.end()
+        {
+            ranges.push(start..=*bound.end());
+        }
+        FreshDb::new(ranges)
+    }
+
+    pub fn is_subset(&self, other: &FreshDb) -> bool {
+        self.difference(other).is_empty()
+    }
+
+    pub fn overlaps(&self, other: &FreshDb) -> bool {
+        !self.intersection(other).is_empty()
+    }
+}
+
+// How many of the original ranges cover each id, as a step function: each
+// `(start, depth)` holds from `start` until the start of the next one, and the
+// first one starts at 0.
+struct Coverage(Vec<(IngredientId, usize)>);
+
+impl Coverage {
+    pub fn new(ranges: &[RangeInclusive<IngredientId>]) -> Coverage {
+        let mut events: Vec<(IngredientId, isize)> = vec![];
+        for range in ranges.iter().filter(|range| !range.is_empty()) {
+            events.push((*range.start(), 1));
+            if let Some(after) = range.end().checked_add(1) {
+                events.push((after, -1));
+            }
+        }
+        events.sort();
+
+        let mut steps = vec![(0, 0)];
+        let mut depth = 0;
+        for events in events.chunk_by(|a, b| a.0 == b.0) {
+            depth += events.iter().map(|(_, change)| change).sum::<isize>();
+            let step = (events[0].0, depth as usize);
+            match steps.last_mut() {
+                Some(last) if last.0 == step.0 => *last = step,
+                _ => steps.push(step),
+            }
+        }
+        steps.dedup_by_key(|(_, depth)| *depth);
+        Coverage(steps)
+    }
+
+    fn segments(&self) -> impl Iterator<Item = (RangeInclusive<IngredientId>, usize)> {
+        self.0.iter().enumerate().map(|(index, (start, depth))| {
+            let end = self
+                .0
+                .get(index + 1)
+                .map_or(IngredientId::MAX, |(next_start, _)| next_start - 1);
+            (*start..=end, *depth)
+        })
+    }
+
+    pub fn depth_at(&self, ingredient_id: IngredientId) -> usize {
+        let index = self.0.partition_point(|(start, _)| *start <= ingredient_id);
+        self.0[index - 1].1
+    }
+
+    // The highest depth, and every range of ids covered that many times.
+    pub fn max_depth(&self) -> (usize, Vec<RangeInclusive<IngredientId>>) {
+        let max_depth = self.0.iter().map(|(_, depth)| *depth).max().unwrap_or(0);
+        let ranges = self
+            .segments()
+            .filter(|(_, depth)| *depth == max_depth)
+            .map(|(range, _)| range)
+            .collect();
+        (max_depth, ranges)
+    }
+
+    pub fn covered_at_least(&self, depth: usize) -> u128 {
+        self.segments()
+            .filter(|(_, segment_depth)| *segment_depth >= depth)
+            .map(|(range, _)| (range.end() - range.start()) as u128 + 1)
             .sum()
     }
 }
 
-fn parse_input(path: &str) -> (FreshDb, Ingredients) {
-    let parts: Vec<String> = fs::read_to_string(path)
-        .unwrap()
-        .split("\n\n")
-        .map(|line| line.to_owned())
-        .collect();
+fn parse_input(path: &str) -> (Vec<RangeInclusive<IngredientId>>, Ingredients) {
+    parse(&fs::read_to_string(path).unwrap())
+}
+
+fn parse_range(line: &str) -> RangeInclusive<IngredientId> {
+    let range: Ingredients = line.split("-").map(|part| part.parse().unwrap()).collect();
+    range[0]..=range[1]
+}
+
+fn parse(content: &str) -> (Vec<RangeInclusive<IngredientId>>, Ingredients) {
+    let parts: Vec<String> = content.split("\n\n").map(|line| line.to_owned()).collect();
+
+    let ranges: Vec<RangeInclusive<IngredientId>> = parts[0].lines().map(parse_range).collect();
 
-    let ranges: Vec<RangeInclusive<IngredientId>> = parts[0]
-        .lines()
-        .map(|line| {
-            let range: Ingredients = line.split("-").map(|part| part.parse().unwrap()).collect();
-            range[0]..=range[1]
+    let ingredients: Ingredients = parts[1].lines().map(|line| line.parse().unwrap()).collect();
+    (ranges, ingredients)
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "3-5
+10-14
+16-20
+12-18
+
+1
+5
+8
+11
+17
+32";
+
+#[test]
+fn test_example() {
+    let (ranges, ingredients) = parse(EXAMPLE);
+    let db = FreshDb::new(ranges);
+
+    assert_eq!(db.ranges(), [3..=5, 10..=20]);
+    assert_eq!(
+        ingredients
+            .iter()
+            .filter(|ingredient| db.contains(ingredient))
+            .count(),
+        3
+    );
+    assert_eq!(db.count(), 14);
+}
+
+#[test]
+fn test_new_normalizes_ranges() {
+    #[allow(clippy::reversed_empty_ranges)]
+    let db = FreshDb::new(vec![
+        20..=25,
+        1..=3,
+        4..=6,
+        10..=12,
+        11..=11,
+        9..=8,
+        u64::MAX - 1..=u64::MAX,
+        u64::MAX..=u64::MAX,
+    ]);
+
+    assert_eq!(
+        db.ranges(),
+        [1..=6, 10..=12, 20..=25, u64::MAX - 1..=u64::MAX]
+    );
+    let contained: Vec<u64> = (0..30).filter(|id| db.contains(id)).collect();
+    assert_eq!(
+        contained,
+        [1, 2, 3, 4, 5, 6, 10, 11, 12, 20, 21, 22, 23, 24, 25]
+    );
+    assert!(db.contains(&u64::MAX));
+    assert_eq!(db.count(), 17);
+    assert_eq!(FreshDb::new(vec![]).count(), 0);
+}
+
+#[cfg(test)]
+fn random_db(state: &mut u64) -> FreshDb {
+    let mut next = |modulo: u64| {
+        *state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (*state >> 33) % modulo
+    };
+    let ranges = (0..next(6))
+        .map(|_| {
+            let start = next(128);
+            start..=(start + next(20)).min(127)
         })
         .collect();
+    FreshDb::new(ranges)
+}
 
-    let ingredients: Ingredients = parts[1].lines().map(|line| line.parse().unwrap()).collect();
-    (FreshDb::new(ranges), ingredients)
+#[cfg(test)]
+fn assert_normalized(db: &FreshDb) {
+    let ranges = db.ranges();
+    assert!(ranges.iter().all(|range| !range.is_empty()));
+    assert!(
+        ranges
+            .windows(2)
+            .all(|pair| pair[0].end() + 1 < *pair[1].start())
+    );
+    assert_eq!(
+        db.count(),
+        ranges
+            .iter()
+            .map(|range| (range.end() - range.start()) as u128 + 1)
+            .sum::<u128>()
+    );
+}
+
+#[cfg(test)]
+fn to_bits(db: &FreshDb) -> u128 {
+    (0..128)
+        .filter(|id| db.contains(id))
+        .fold(0, |bits, id| bits | 1 << id)
+}
+
+#[test]
+fn test_interval_algebra_matches_bitsets() {
+    let mut state = 5;
+    for _ in 0..500 {
+        let (a, b) = (random_db(&mut state), random_db(&mut state));
+        let (a_bits, b_bits) = (to_bits(&a), to_bits(&b));
+        let bound = FreshDb::new(vec![5..=100]);
+
+        for (result, expected) in [
+            (a.union(&b), a_bits | b_bits),
+            (a.intersection(&b), a_bits & b_bits),
+            (a.difference(&b), a_bits & !b_bits),
+            (a.symmetric_difference(&b), a_bits ^ b_bits),
+            (a.complement(5..=100), !a_bits & to_bits(&bound)),
+        ] {
+            assert_eq!(to_bits(&result), expected);
+            assert_normalized(&result);
+        }
+        assert_eq!(a.is_subset(&b), a_bits & !b_bits == 0);
+        assert_eq!(a.overlaps(&b), a_bits & b_bits != 0);
+    }
+}
+
+#[test]
+fn test_complement_edges() {
+    let db = FreshDb::new(vec![0..=3, 10..=u64::MAX]);
+
+    assert_eq!(db.complement(0..=u64::MAX).ranges(), [4..=9]);
+    assert_eq!(db.complement(2..=12).ranges(), [4..=9]);
+    assert_eq!(db.complement(5..=7).ranges(), [5..=7]);
+    assert!(db.complement(20..=30).is_empty());
+    assert_eq!(
+        FreshDb::new(vec![]).complement(0..=u64::MAX).ranges(),
+        [0..=u64::MAX]
+    );
+}
+
+#[test]
+fn test_insert_and_remove_ranges_match_bitsets() {
+    let mut state: u64 = 9;
+    let mut next = |modulo: u64| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) % modulo
+    };
+
+    let mut db = FreshDb::default();
+    let mut bits: u128 = 0;
+    for _ in 0..2000 {
+        let start = next(128);
+        let end = (start + next(16)).min(127);
+        let range_bits = (start..=end).fold(0, |bits, id| bits | 1 << id);
+        if next(3) == 0 {
+            db.remove_range(start..=end);
+            bits &= !range_bits;
+        } else {
+            db.insert_range(start..=end);
+            bits |= range_bits;
+        }
+        assert_eq!(to_bits(&db), bits);
+        assert_normalized(&db);
+    }
+}
+
+#[test]
+fn test_remove_range_edges() {
+    let mut db = FreshDb::new(vec![0..=10, 20..=u64::MAX]);
+
+    db.remove_range(0..=0);
+    db.remove_range(u64::MAX..=u64::MAX);
+    db.remove_range(5..=25);
+    assert_eq!(db.ranges(), [1..=4, 26..=u64::MAX - 1]);
+
+    db.insert_range(5..=25);
+    assert_eq!(db.ranges(), [1..=u64::MAX - 1]);
+    assert_eq!(db.count(), u64::MAX as u128 - 1);
+    assert_eq!(
+        FreshDb::new(vec![0..=u64::MAX]).count(),
+        u64::MAX as u128 + 1
+    );
+}
+
+#[test]
+fn test_coverage_example() {
+    let (ranges, _) = parse(EXAMPLE);
+    let coverage = Coverage::new(&ranges);
+
+    assert_eq!(coverage.depth_at(2), 0);
+    assert_eq!(coverage.depth_at(4), 1);
+    assert_eq!(coverage.depth_at(13), 2);
+    assert_eq!(coverage.depth_at(21), 0);
+    assert_eq!(coverage.max_depth(), (2, vec![12..=14, 16..=18]));
+    assert_eq!(coverage.covered_at_least(1), 14);
+    assert_eq!(coverage.covered_at_least(2), 6);
+    assert_eq!(coverage.covered_at_least(3), 0);
+}
+
+#[test]
+fn test_coverage_matches_brute_force() {
+    let mut state: u64 = 13;
+    let mut next = |modulo: u64| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) % modulo
+    };
+
+    for _ in 0..200 {
+        let ranges: Vec<RangeInclusive<IngredientId>> = (0..next(8))
+            .map(|_| {
+                let start = next(64);
+                start..=start + next(12)
+            })
+            .collect();
+        let depths: Vec<usize> = (0..80)
+            .map(|id| ranges.iter().filter(|range| range.contains(&id)).count())
+            .collect();
+        let coverage = Coverage::new(&ranges);
+
+        for (id, depth) in depths.iter().enumerate() {
+            assert_eq!(coverage.depth_at(id as IngredientId), *depth);
+        }
+        let max_depth = *depths.iter().max().unwrap();
+        let (depth, max_ranges) = coverage.max_depth();
+        assert_eq!(depth, max_depth);
+        if max_depth > 0 {
+            let max_ids: Vec<IngredientId> = max_ranges.into_iter().flatten().collect();
+            let expected: Vec<IngredientId> = (0..80)
+                .filter(|id| depths[*id as usize] == max_depth)
+                .collect();
+            assert_eq!(max_ids, expected);
+        }
+        for k in 1..=max_depth + 1 {
+            assert_eq!(
+                coverage.covered_at_least(k),
+                depths.iter().filter(|depth| **depth >= k).count() as u128
+            );
+        }
+        assert_eq!(coverage.covered_at_least(1), FreshDb::new(ranges).count());
+    }
+}
+
+#[test]
+fn test_coverage_edges() {
+    let coverage = Coverage::new(&[0..=u64::MAX, u64::MAX - 1..=u64::MAX, 0..=0]);
+
+    assert_eq!(coverage.depth_at(0), 2);
+    assert_eq!(coverage.depth_at(1), 1);
+    assert_eq!(coverage.depth_at(u64::MAX), 2);
+    assert_eq!(
+        coverage.max_depth(),
+        (2, vec![0..=0, u64::MAX - 1..=u64::MAX])
+    );
+    assert_eq!(coverage.covered_at_least(0), u64::MAX as u128 + 1);
+    assert_eq!(coverage.covered_at_least(2), 3);
+    assert_eq!(Coverage::new(&[]).max_depth(), (0, vec![0..=u64::MAX]));
 }